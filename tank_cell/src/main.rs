@@ -1,4 +1,8 @@
-use std::{collections::HashSet, ffi::CString, ptr::null};
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    ptr::{null, null_mut},
+};
 
 use clap::{App, Arg};
 use libc::*;
@@ -24,6 +28,22 @@ fn main() {
                 .help("set time limit(s) for code")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("file_limit")
+                .long("file_limit")
+                .help("set size limit(MB) of each file written by code")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_address_limit")
+                .long("no_address_limit")
+                .help("do not limit address space, which runtimes like JVM reserve far more than they use"),
+        )
+        .arg(
+            Arg::with_name("trace_limits")
+                .long("trace_limits")
+                .help("trace processes started by code, ending by the signal of a limit any of them hits"),
+        )
         .arg(
             Arg::with_name("path")
                 .index(1)
@@ -70,12 +90,30 @@ fn main() {
     // set memory and time limit
     if let Some(memory_limit) = cmd.value_of("memory_limit") {
         let memory_limit = memory_limit.trim().parse::<u64>().unwrap();
-        set_memory_limit(memory_limit);
+        if !cmd.is_present("no_address_limit") {
+            set_memory_limit(memory_limit);
+        }
     }
     if let Some(time_limit) = cmd.value_of("time_limit") {
         let time_limit = time_limit.trim().parse::<u64>().unwrap();
         set_time_limit(time_limit);
     }
+    if let Some(file_limit) = cmd.value_of("file_limit") {
+        let file_limit = file_limit.trim().parse::<u64>().unwrap();
+        set_file_limit(file_limit);
+    }
+
+    if cmd.is_present("trace_limits") {
+        unsafe {
+            let child = fork();
+            if child > 0 {
+                trace_limits(child);
+            }
+            // stop until the cell is ready to trace
+            ptrace(PTRACE_TRACEME, 0, null_mut::<c_void>(), null_mut::<c_void>());
+            raise(SIGSTOP);
+        }
+    }
 
     let raw_params = raw_params.as_ptr();
     let _act_bannd = SCMP_ACT_ERRNO(998244353); // a more soft way to block operation
@@ -94,6 +132,17 @@ fn main() {
         if rules.contains("io") {
             load_io_rules(context);
         }
+        if rules.contains("threads") {
+            load_thread_rules(context);
+        }
+        if rules.contains("jvm") {
+            load_thread_rules(context);
+            load_jvm_rules(context);
+        }
+        if rules.contains("go") {
+            load_thread_rules(context);
+            load_go_rules(context);
+        }
 
         // rule for execve allowing only being used by us
         let exe = path.to_str().unwrap().as_ptr() as *const i8;
@@ -118,6 +167,63 @@ fn main() {
     }
 }
 
+/// Signals of limits, by which a process is terminated.
+const LIMIT_SIGNALS: [c_int; 2] = [SIGXCPU, SIGXFSZ];
+
+/// Trace `child` and all processes it starts, and end as `child` ends, or by the signal of a limit
+/// once any of them is terminated by it, like `as` started by g++, which g++ tells in text only.
+unsafe fn trace_limits(child: pid_t) -> ! {
+    let mut status: c_int = 0;
+    waitpid(child, &mut status, 0);
+    let options = PTRACE_O_TRACEFORK
+        | PTRACE_O_TRACEVFORK
+        | PTRACE_O_TRACECLONE
+        | PTRACE_O_TRACEEXEC
+        | PTRACE_O_EXITKILL;
+    ptrace(PTRACE_SETOPTIONS, child, null_mut::<c_void>(), options as *mut c_void);
+    ptrace(PTRACE_CONT, child, null_mut::<c_void>(), null_mut::<c_void>());
+
+    let mut limit_signal = None;
+    loop {
+        let pid = waitpid(-1, &mut status, __WALL);
+        if pid < 0 {
+            break;
+        }
+        if WIFSTOPPED(status) {
+            // stops of ptrace events and of new processes are not signals to pass on
+            let signal = WSTOPSIG(status);
+            let signal = if status >> 16 != 0 || signal == SIGSTOP {
+                0
+            } else {
+                signal
+            };
+            ptrace(PTRACE_CONT, pid, null_mut::<c_void>(), signal as *mut c_void);
+            continue;
+        }
+        if WIFSIGNALED(status) && LIMIT_SIGNALS.contains(&WTERMSIG(status)) {
+            limit_signal = Some(WTERMSIG(status));
+        }
+        // processes left behind are killed once the cell exits
+        if pid == child {
+            break;
+        }
+    }
+
+    match limit_signal {
+        Some(signal) => raise_default(signal),
+        None if WIFSIGNALED(status) => raise_default(WTERMSIG(status)),
+        None => exit(WEXITSTATUS(status)),
+    }
+}
+
+/// Terminate the cell by `sig`, as its default action does.
+unsafe fn raise_default(sig: c_int) -> ! {
+    signal(sig, SIG_DFL);
+    raise(sig);
+    // signals ignored by default, which never end a process
+    exit(128 + sig)
+}
+
 unsafe fn allow_syscall(ctx: *mut c_void, ids: Vec<i64>) {
     for id in ids {
         assert!(seccomp_rule_add(ctx, SCMP_ACT_ALLOW, id as i32, 0) == 0);
@@ -169,6 +275,8 @@ unsafe fn load_minimum_rules(ctx: *mut c_void) {
             SYS_getgid,
             SYS_lstat,
             SYS_exit_group,
+            // rust checks standard streams by it at start
+            SYS_poll,
         ],
     );
 
@@ -205,6 +313,85 @@ unsafe fn load_io_rules(ctx: *mut c_void) {
     allow_syscall(ctx, vec![SYS_openat, SYS_open]);
 }
 
+unsafe fn load_thread_rules(ctx: *mut c_void) {
+    // rseq is not known by libc yet, while glibc registers it for every thread
+    const SYS_RSEQ: i64 = 334;
+    allow_syscall(
+        ctx,
+        vec![
+            SYS_gettid,
+            SYS_getpid,
+            SYS_tgkill,
+            SYS_sched_yield,
+            SYS_sched_getaffinity,
+            SYS_madvise,
+            SYS_nanosleep,
+            SYS_clock_nanosleep,
+            SYS_clock_gettime,
+            SYS_clock_getres,
+            SYS_rt_sigreturn,
+            SYS_RSEQ,
+        ],
+    );
+
+    // rule for clone allowing threads only, not new processes
+    seccomp_rule_add(
+        ctx,
+        SCMP_ACT_ALLOW,
+        SYS_clone as i32,
+        1,
+        scmp_arg_cmp {
+            arg: 0,
+            op: scmp_compare::SCMP_CMP_MASKED_EQ,
+            datum_a: libc::CLONE_THREAD as u64,
+            datum_b: libc::CLONE_THREAD as u64,
+        },
+    );
+    // flags of clone3 are behind a pointer which seccomp can not check,
+    // so pretend it is missing and let libc fall back to clone
+    seccomp_rule_add(ctx, SCMP_ACT_ERRNO(ENOSYS as u32), SYS_clone3 as i32, 0);
+}
+
+unsafe fn load_jvm_rules(ctx: *mut c_void) {
+    // faccessat2 is not known by libc yet
+    const SYS_FACCESSAT2: i64 = 439;
+    allow_syscall(
+        ctx,
+        vec![
+            SYS_uname,
+            SYS_getcwd,
+            SYS_prctl,
+            SYS_newfstatat,
+            SYS_faccessat,
+            SYS_FACCESSAT2,
+            SYS_getrusage,
+        ],
+    );
+
+    // JVM looks up the name of user through nscd, which is refused softly
+    for id in [SYS_socket, SYS_connect] {
+        seccomp_rule_add(ctx, SCMP_ACT_ERRNO(EACCES as u32), id as i32, 0);
+    }
+}
+
+unsafe fn load_go_rules(ctx: *mut c_void) {
+    allow_syscall(
+        ctx,
+        vec![
+            // limit of open files is raised at start, never above the hard one
+            SYS_getrlimit,
+            SYS_setrlimit,
+            SYS_uname,
+            // timers of the runtime are driven by a poller of its own
+            SYS_epoll_create1,
+            SYS_epoll_ctl,
+            SYS_epoll_pwait,
+            SYS_eventfd2,
+            SYS_pipe2,
+        ],
+    );
+}
+
 fn set_memory_limit(lim: u64) {
     let ctx = rlimit64 {
         rlim_cur: lim << 10 << 10 << 1,
@@ -225,3 +412,13 @@ fn set_time_limit(lim: u64) {
         assert!(setrlimit64(RLIMIT_CPU, ctx) == 0);
     }
 }
+fn set_file_limit(lim: u64) {
+    let ctx = rlimit64 {
+        rlim_cur: lim << 10 << 10,
+        rlim_max: lim << 10 << 10,
+    };
+    let ctx: *const rlimit64 = &ctx;
+    unsafe {
+        assert!(setrlimit64(RLIMIT_FSIZE, ctx) == 0);
+    }
+}
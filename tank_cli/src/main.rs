@@ -1,12 +1,16 @@
-use core::panic;
 use std::{fs, path::Path};
 
 use clap::Clap;
-use compile::CompilerDescriptor;
-use tank_core::{compile::{self, CompileResult, CompiledProgram, Compiler}, error::{Error, Result}};
+use tank_core::{compile::{cache::CompileCache, prepare_jury_program, CompileDiagnostics, CompileResult, CompiledProgram, CompilerEnvironmentStatus, CompilerOptions, CompilerRegistry}, error::{Error, Result}, JudgeResult};
 use tank_core::{
-    problem::{ComparisionModeConfig, LimitConfig, ProblemConfig},
-    judge::{launch_interactive_case_judge, launch_normal_case_judge, launch_special_case_judge},
+    problem::{
+        CommunicationWiring, ComparisionModeConfig, GraderConfig, LimitConfig, ProblemConfig,
+        DEFAULT_CHECKER_LIMIT,
+    },
+    judge::{
+        compile_checker_script, launch_communication_case_judge, launch_interactive_case_judge,
+        launch_normal_case_judge, launch_script_case_judge, launch_special_case_judge,
+    },
 };
 #[derive(Clap)]
 #[clap(
@@ -16,6 +20,18 @@ use tank_core::{
     about = "A fast and reliable judge container wrtten in Rust."
 )]
 struct Opts {
+    #[clap(short, long, about = "language of source, told by its suffix if not given")]
+    language: Option<String>,
+    #[clap(long, about = "YAML file defining more languages, `languages.yaml` beside tank_cli by default")]
+    language_config: Option<String>,
+    #[clap(long = "std", about = "standard of language, like c++20 or c11")]
+    standard: Option<String>,
+    #[clap(long, possible_values = &["gcc", "clang"], about = "compiler of C and C++")]
+    backend: Option<String>,
+    #[clap(long, allow_hyphen_values = true, about = "extra flags of compiler, separated by spaces")]
+    flags: Option<String>,
+    #[clap(long, default_value = "1024", about = "size(MB) of the cache where compiled sources are reused")]
+    cache_size: u64,
     #[clap(subcommand)]
     subcmd: SubCommand,
 }
@@ -28,12 +44,20 @@ enum SubCommand {
     Normal(NormalJudgeConfig),
     #[clap(version = "0.2.0", about = "Judge in special mode")]
     Special(SpecialJudgeConfig),
+    #[clap(version = "0.1.0", about = "Judge by checker script")]
+    Script(ScriptJudgeConfig),
     #[clap(version = "0.2.0", about = "Judge in interactive mode")]
     Interactive(InteractiveJudgeConfig),
+    #[clap(version = "0.1.0", about = "Judge in communication mode")]
+    Communication(CommunicationJudgeConfig),
     #[clap(version = "0.2.0", about = "Judge using config.yaml")]
     Prefab(PrefabJudgeConfig),
+    #[clap(version = "0.1.0", about = "Judge submitted outputs using config.yaml")]
+    Output(OutputJudgeConfig),
     #[clap(version = "0.1.0", about = "Lint problem using config.yaml")]
     Lint(LintConfig),
+    #[clap(version = "0.1.0", about = "List languages and their compilers")]
+    Languages,
     #[clap(version = "0.1.0", about = "Debug mode")]
     Debug,
 }
@@ -59,9 +83,13 @@ struct NormalJudgeConfig {
     #[clap(
         short,
         default_value = "line",
-        about = "compare method: full, line, value.\nrefer to document for more details."
+        about = "compare method: full, line, value, float, unordered-lines, unordered-tokens, value-ignore-case, regex.\nrefer to document for more details."
     )]
     compare_method: String,
+    #[clap(long, default_value = "1e-6", about = "absolute error allowed in float mode")]
+    abs_eps: f64,
+    #[clap(long, default_value = "1e-6", about = "relative error allowed in float mode")]
+    rel_eps: f64,
 }
 
 #[derive(Clap, Debug)]
@@ -72,6 +100,24 @@ struct SpecialJudgeConfig {
     src_path: String,
     #[clap(short, about = "input file path")]
     input_file: String,
+    #[clap(short, about = "answer file path, passed to checker")]
+    answer_file: Option<String>,
+    #[clap(short, default_value = "1024", about = "memory limit(MB)")]
+    memory_limit: u64,
+    #[clap(short, default_value = "30000", about = "time limit(MS)")]
+    time_limit: u64,
+}
+
+#[derive(Clap, Debug)]
+struct ScriptJudgeConfig {
+    #[clap(about = "checker script path")]
+    checker: String,
+    #[clap(about = "path of code")]
+    src_path: String,
+    #[clap(short, about = "input file path")]
+    input_file: String,
+    #[clap(short, about = "answer file path, passed to checker")]
+    answer_file: Option<String>,
     #[clap(short, default_value = "1024", about = "memory limit(MB)")]
     memory_limit: u64,
     #[clap(short, default_value = "30000", about = "time limit(MS)")]
@@ -86,6 +132,32 @@ struct InteractiveJudgeConfig {
     src_path: String,
     #[clap(short, about = "input file path")]
     input_file: Option<String>,
+    #[clap(long, about = "file to save the transcript of interaction")]
+    transcript: Option<String>,
+    #[clap(long, about = "allow interactor to request at most this many runs of program")]
+    max_runs: Option<u64>,
+    #[clap(short, default_value = "1024", about = "memory limit(MB)")]
+    memory_limit: u64,
+    #[clap(short, default_value = "30000", about = "time limit(MS)")]
+    time_limit: u64,
+}
+
+#[derive(Clap, Debug)]
+struct CommunicationJudgeConfig {
+    #[clap(about = "manager code")]
+    manager: String,
+    #[clap(required = true, about = "paths of programs to run, one for each process, like an encoder and a decoder")]
+    src_paths: Vec<String>,
+    #[clap(short, about = "input file path")]
+    input_file: Option<String>,
+    #[clap(short = 'n', about = "number of processes when a single program runs as all of them, 2 by default")]
+    processes: Option<u64>,
+    #[clap(
+        short,
+        default_value = "star",
+        about = "how processes are connected: star, chain.\nrefer to document for more details."
+    )]
+    wiring: String,
     #[clap(short, default_value = "1024", about = "memory limit(MB)")]
     memory_limit: u64,
     #[clap(short, default_value = "30000", about = "time limit(MS)")]
@@ -100,6 +172,14 @@ struct PrefabJudgeConfig {
     src_path: String,
 }
 
+#[derive(Clap, Debug)]
+struct OutputJudgeConfig {
+    #[clap(about = "problem config")]
+    config: String,
+    #[clap(about = "directory or zip of outputs")]
+    outputs: String,
+}
+
 #[derive(Clap, Debug)]
 struct LintConfig {
     #[clap(about = "problem config")]
@@ -111,6 +191,30 @@ struct DebugJudge {}
 
 fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
+    let mut registry = CompilerRegistry::default();
+    let default_language_config = std::env::current_exe()?
+        .with_file_name("languages.yaml")
+        .to_string_lossy()
+        .to_string();
+    match &opts.language_config {
+        Some(path) => registry.load_languages(path)?,
+        None if Path::new(&default_language_config).exists() => {
+            registry.load_languages(&default_language_config)?
+        }
+        None => {}
+    }
+    let language = opts.language.as_deref();
+    let options = CompilerOptions {
+        standard: opts.standard.clone(),
+        backend: opts.backend.as_deref().map(str::parse).transpose()?,
+        flags: opts
+            .flags
+            .as_deref()
+            .map(|f| f.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+        limit: None,
+    };
+    let cache = CompileCache::with_size(opts.cache_size << 20);
 
     match opts.subcmd {
         SubCommand::Normal(config) => {
@@ -118,13 +222,21 @@ fn main() -> Result<()> {
                 "full" => ComparisionModeConfig::Full,
                 "line" => ComparisionModeConfig::Line,
                 "value" => ComparisionModeConfig::Value,
+                "float" => ComparisionModeConfig::Float {
+                    abs_eps: config.abs_eps,
+                    rel_eps: config.rel_eps,
+                },
+                "unordered-lines" => ComparisionModeConfig::UnorderedLines,
+                "unordered-tokens" => ComparisionModeConfig::UnorderedTokens,
+                "value-ignore-case" => ComparisionModeConfig::ValueIgnoreCase,
+                "regex" => ComparisionModeConfig::Regex,
                 _ => Err(Error::Argument("comparation mode not found".into()))?,
             };
 
-            let compiler=compile(&config.src_path);
+            let program=compile(&cache, &registry, &config.src_path, language, &options)?;
 
             let judge_result = launch_normal_case_judge(
-                compiler.1,
+                program,
                 &config.input_file,
                 &config.answer_file,
                 LimitConfig {
@@ -134,14 +246,33 @@ fn main() -> Result<()> {
                 &comparision_mode,
             )?;
             println!("{:#?}", judge_result);
+            print_difference(&judge_result);
         }
         SubCommand::Special(config) => {
-            let compiler=compile(&config.src_path);
+            let program=compile(&cache, &registry, &config.src_path, language, &options)?;
 
             let judge_result = launch_special_case_judge(
-                compiler.1,
+                program,
+                &config.input_file,
+                config.answer_file.as_deref(),
+                &prepare_jury_program(&config.checker)?,
+                LimitConfig {
+                    time_limit: config.time_limit,
+                    memory_limit: config.memory_limit,
+                },
+                DEFAULT_CHECKER_LIMIT,
+            )?;
+            println!("{:#?}", judge_result);
+        }
+        SubCommand::Script(config) => {
+            let checker = compile_checker_script(&fs::read_to_string(&config.checker)?)?;
+            let program=compile(&cache, &registry, &config.src_path, language, &options)?;
+
+            let judge_result = launch_script_case_judge(
+                program,
                 &config.input_file,
-                &config.checker,
+                config.answer_file.as_deref(),
+                &checker,
                 LimitConfig {
                     time_limit: config.time_limit,
                     memory_limit: config.memory_limit,
@@ -150,29 +281,96 @@ fn main() -> Result<()> {
             println!("{:#?}", judge_result);
         }
         SubCommand::Prefab(config) => {
-            let compiler=compile(&config.src_path);
+            let problem = ProblemConfig::from_file(&config.config)?;
+            let found = registry.resolve(&config.src_path, language)?;
+            // options given by command line take precedence over those of the problem
+            let options = match problem.compilers.get(&found.id) {
+                Some(problem_options) => options.clone().or(problem_options),
+                None => options.clone(),
+            };
+            let grader = problem.grader_of(&found.id)?;
+            let program=compile_with_grader(&cache, &registry, &config.src_path, language, &options, grader.as_ref())?;
             
             // TOOD: judge should use compiledprogram instead of str
-            let judge_result = ProblemConfig::from_file(&config.config)?.judge(compiler.1)?;
+            let judge_result = problem.judge(program)?;
             println!("{:#?}", judge_result);
+            judge_result.iter().for_each(print_difference);
+        }
+        SubCommand::Output(config) => {
+            let judge_result = ProblemConfig::from_file(&config.config)?.judge_outputs(&config.outputs)?;
+            println!("{:#?}", judge_result);
+            judge_result.iter().for_each(print_difference);
         }
         SubCommand::Interactive(config) => {
-            let compiler=compile(&config.src_path);
+            let program=compile(&cache, &registry, &config.src_path, language, &options)?;
             
             let judge_result = launch_interactive_case_judge(
-                compiler.1,
+                program,
                 config.input_file,
-                &config.interactor,
+                &prepare_jury_program(&config.interactor)?,
                 LimitConfig {
                     time_limit: config.time_limit,
                     memory_limit: config.memory_limit,
                 },
+                DEFAULT_CHECKER_LIMIT,
+                config.transcript.as_deref(),
+                config.max_runs,
             );
             println!("{:#?}", judge_result);
         }
+        SubCommand::Communication(config) => {
+            let wiring = match config.wiring.as_str() {
+                "star" => CommunicationWiring::Star,
+                "chain" => CommunicationWiring::Chain,
+                _ => Err(Error::Argument("wiring not found".into()))?,
+            };
+
+            let mut programs=Vec::new();
+            for src_path in &config.src_paths{
+                programs.push(compile(&cache, &registry, src_path, language, &options)?);
+            }
+            let programs=match (programs.len(), config.processes){
+                (1, processes)=>vec![programs.remove(0); processes.unwrap_or(2) as usize],
+                (count, Some(processes)) if count as u64!=processes=>{
+                    Err(Error::Argument(format!("{} programs for {} processes", count, processes)))?
+                }
+                _=>programs,
+            };
+
+            let judge_result = launch_communication_case_judge(
+                programs,
+                config.input_file,
+                &prepare_jury_program(&config.manager)?,
+                wiring,
+                LimitConfig {
+                    time_limit: config.time_limit,
+                    memory_limit: config.memory_limit,
+                },
+                DEFAULT_CHECKER_LIMIT,
+            );
+            println!("{:#?}", judge_result);
+        }
+        SubCommand::Languages => {
+            for (language, status) in registry.available_languages() {
+                match status {
+                    CompilerEnvironmentStatus::OK { version, path } => println!(
+                        "{}\t{}\t{}\t{}",
+                        language.id,
+                        language.suffixes.join(","),
+                        version,
+                        path
+                    ),
+                    CompilerEnvironmentStatus::Missing => println!(
+                        "{}\t{}\tmissing",
+                        language.id,
+                        language.suffixes.join(",")
+                    ),
+                }
+            }
+        }
         SubCommand::Debug => {}
         SubCommand::Compile(config) => {
-            let _compiler=compile(&config.file);
+            compile(&cache, &registry, &config.file, language, &options)?;
         }
         
         SubCommand::Lint(config) => {
@@ -184,28 +382,48 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn compile(file:&str)->(Box<dyn Compiler>,CompiledProgram){
+/// Show where output differs from answer as a short colored diff.
+fn print_difference(result: &JudgeResult) {
+    if let Some(message) = &result.message {
+        for line in message.lines() {
+            if line.starts_with("expected") {
+                println!("\x1b[32m+ {}\x1b[0m", line);
+            } else if line.starts_with("found") {
+                println!("\x1b[31m- {}\x1b[0m", line);
+            } else {
+                println!("\x1b[1m{}\x1b[0m", line);
+            }
+        }
+    }
+}
+
+fn compile(cache:&CompileCache,registry:&CompilerRegistry,file:&str,language:Option<&str>,options:&CompilerOptions)->Result<CompiledProgram>{
+    compile_with_grader(cache, registry, file, language, options, None)
+}
+
+/// Compile a source, or reuse what was compiled from the same one in `cache`.
+fn compile_with_grader(cache:&CompileCache,registry:&CompilerRegistry,file:&str,language:Option<&str>,options:&CompilerOptions,grader:Option<&GraderConfig>)->Result<CompiledProgram>{
     let path=Path::new(file);
     let src=fs::read_to_string(path.canonicalize().unwrap()).unwrap();
-    let extension=path.extension().unwrap().to_str().unwrap();
-
-    let compiler:Box<dyn Compiler>;
-    let result;
-    match extension {
-        _ if compile::gpp::CompilerGPP::support_sufix().contains(&extension)=>{
-            compiler=Box::new(compile::gpp::CompilerGPP::new().unwrap());
-            result=compiler.compile(src);
+
+    let language=registry.resolve(file, language)?;
+    match cache.compile(language, options, src, grader)?{
+        CompileResult::OK(program)=>Ok(program),
+        CompileResult::CompileError(diagnostics)=>{
+            print_diagnostics(&diagnostics);
+            Err(Error::UserProgram(format!("failed to compile file `{}`",file)))
         }
-        _ if compile::python::CompilerPython::support_sufix().contains(&extension)=>{
-            compiler=Box::new(compile::python::CompilerPython::new().unwrap());
-            result=compiler.compile(src);
+        CompileResult::LimitExceeded=>{
+            Err(Error::UserProgram(format!("compiler exceeds limits on file `{}`",file)))
         }
-        _=>unimplemented!()
     }
+}
 
-    if let CompileResult::OK(program)=result{
-        return (compiler,program);
-    }else{
-        panic!("failed to compile file `{}`: {:#?}",file,result);
+/// Show what the compiler tells, with its status in bold.
+fn print_diagnostics(diagnostics: &CompileDiagnostics) {
+    let mut lines = diagnostics.to_string();
+    if let Some(end) = lines.find('\n') {
+        lines.insert_str(end, "\x1b[0m");
     }
-}
\ No newline at end of file
+    eprintln!("\x1b[1m{}", lines);
+}
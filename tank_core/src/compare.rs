@@ -1,35 +1,490 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, BufRead, Read},
+};
+
+use regex::Regex;
+
 use crate::JudgeStatus;
 
+/// Chars kept on each side of the differing column in an excerpt.
+const EXCERPT_RADIUS: usize = 20;
+
+/// Part of a text around where it differs from the other, located by line and column counted from 1.
+#[derive(Debug, Clone)]
+pub struct Excerpt {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+/// The first place output differs from answer, `None` for the end of a text.
+#[derive(Debug, Clone)]
+pub struct Difference {
+    /// index of token counted from 1, given by modes comparing tokens.
+    pub token: Option<usize>,
+    pub expected: Option<Excerpt>,
+    pub found: Option<Excerpt>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(token) = self.token {
+            writeln!(f, "token {} differs", token)?;
+        }
+        for (name, excerpt, end) in [
+            ("expected", &self.expected, "end of answer"),
+            ("found", &self.found, "end of output"),
+        ]
+        .iter()
+        {
+            match excerpt {
+                Some(excerpt) => writeln!(
+                    f,
+                    "{} (line {}, column {}): {}",
+                    name, excerpt.line, excerpt.column, excerpt.text
+                )?,
+                None => writeln!(f, "{}: {}", name, end)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 pub enum ComparisionResult {
     Same,
-    Different,
-    PatternDifferent,
+    Different(Difference),
+    PatternDifferent(Difference),
+}
+
+impl ComparisionResult {
+    pub fn difference(&self) -> Option<&Difference> {
+        match self {
+            ComparisionResult::Same => None,
+            ComparisionResult::Different(difference) => Some(difference),
+            ComparisionResult::PatternDifferent(difference) => Some(difference),
+        }
+    }
 }
 
 impl Into<JudgeStatus> for ComparisionResult{
     fn into(self) -> JudgeStatus {
         match self {
             ComparisionResult::Same => JudgeStatus::Accept,
-            ComparisionResult::Different => JudgeStatus::WrongAnswer,
-            ComparisionResult::PatternDifferent => JudgeStatus::PresentationError,
+            ComparisionResult::Different(_) => JudgeStatus::WrongAnswer,
+            ComparisionResult::PatternDifferent(_) => JudgeStatus::PresentationError,
         }
     }
 }
 
+/// A way to compare output with answer.
+///
+/// Both texts are read incrementally, and modes keep at most a line or a token of each of them at a time,
+/// except `UnorderedLinesCompare`, which has to remember all lines.
 pub trait ComparisionMode {
-    fn compare(&self,str1: &String, str2: &String) -> ComparisionResult;
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult>;
+
+    fn compare(&self, str1: &String, str2: &String) -> ComparisionResult {
+        // reading from memory never fails
+        self.compare_stream(&mut str1.as_bytes(), &mut str2.as_bytes())
+            .unwrap()
+    }
+}
+
+/// Bytes of the current line kept for excerpts, enough for `EXCERPT_RADIUS` chars and a broken one.
+const RECENT_BYTES: usize = 4 * (EXCERPT_RADIUS + 2);
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+/// Cut a line short around `column`.
+fn excerpt_of_line(text: &str, line: usize, column: usize) -> Excerpt {
+    let chars: Vec<char> = text.chars().collect();
+    let from = (column - 1).saturating_sub(EXCERPT_RADIUS);
+    let to = (column - 1 + EXCERPT_RADIUS).min(chars.len()).max(from);
+    let mut excerpt: String = chars[from..to].iter().collect();
+    if from > 0 {
+        excerpt.insert_str(0, "...");
+    }
+    if to < chars.len() {
+        excerpt.push_str("...");
+    }
+
+    Excerpt {
+        line,
+        column,
+        text: excerpt,
+    }
+}
+
+/// Reads a text byte by byte, knowing where it is.
+struct Cursor<'a> {
+    reader: &'a mut dyn BufRead,
+    /// bytes read ahead for an excerpt, but not consumed yet.
+    ahead: VecDeque<u8>,
+    /// the last bytes consumed in the current line.
+    recent: VecDeque<u8>,
+    /// whether bytes at the beginning of the current line are dropped from `recent`.
+    recent_cut: bool,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(reader: &'a mut dyn BufRead) -> Self {
+        Self {
+            reader,
+            ahead: VecDeque::new(),
+            recent: VecDeque::new(),
+            recent_cut: false,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        if let Some(&byte) = self.ahead.front() {
+            return Ok(Some(byte));
+        }
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) -> io::Result<Option<u8>> {
+        let byte = match self.ahead.pop_front() {
+            Some(byte) => Some(byte),
+            None => {
+                let byte = self.reader.fill_buf()?.first().copied();
+                if byte.is_some() {
+                    self.reader.consume(1);
+                }
+                byte
+            }
+        };
+
+        match byte {
+            Some(b'\n') => {
+                self.line += 1;
+                self.column = 1;
+                self.recent.clear();
+                self.recent_cut = false;
+            }
+            Some(byte) => {
+                if !is_continuation(byte) {
+                    self.column += 1;
+                }
+                self.recent.push_back(byte);
+                if self.recent.len() > RECENT_BYTES {
+                    self.recent.pop_front();
+                    self.recent_cut = true;
+                }
+            }
+            None => {}
+        }
+        Ok(byte)
+    }
+
+    fn skip_whitespace(&mut self) -> io::Result<()> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                break;
+            }
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    /// Bytes until the next whitespace, which may be none.
+    fn rest_of_token(&mut self) -> io::Result<Vec<u8>> {
+        let mut token = Vec::new();
+        while let Some(byte) = self.peek()? {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            token.push(byte);
+            self.bump()?;
+        }
+        Ok(token)
+    }
+
+    /// The next token with where it begins, `None` at the end of text.
+    fn next_token(&mut self) -> io::Result<Option<(usize, usize, Vec<u8>)>> {
+        self.skip_whitespace()?;
+        if self.peek()?.is_none() {
+            return Ok(None);
+        }
+        let (line, column) = (self.line, self.column);
+        Ok(Some((line, column, self.rest_of_token()?)))
+    }
+
+    /// The rest of the current line without line feed, `None` at the end of text.
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        if self.peek()?.is_none() {
+            return Ok(None);
+        }
+        let mut line = Vec::new();
+        while let Some(byte) = self.bump()? {
+            if byte == b'\n' {
+                break;
+            }
+            line.push(byte);
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+
+    /// Excerpt around the char at the cursor, reading ahead without consuming anything.
+    fn excerpt(&mut self) -> io::Result<Excerpt> {
+        while self.ahead.len() < RECENT_BYTES && !self.ahead.contains(&b'\n') {
+            let byte = match self.reader.fill_buf()?.first().copied() {
+                Some(byte) => byte,
+                None => break,
+            };
+            self.reader.consume(1);
+            self.ahead.push_back(byte);
+        }
+
+        // a char may be cut by the cursor, when two texts differ in its later bytes
+        let mut split = self.recent.len();
+        if self.ahead.front().map_or(false, |f| is_continuation(*f)) {
+            while split > 0 && is_continuation(self.recent[split - 1]) {
+                split -= 1;
+            }
+            split = split.saturating_sub(1);
+        }
+        let column = if split < self.recent.len() {
+            self.column - 1
+        } else {
+            self.column
+        };
+
+        let before: Vec<u8> = self
+            .recent
+            .iter()
+            .take(split)
+            .skip_while(|f| is_continuation(**f))
+            .copied()
+            .collect();
+        let after: Vec<u8> = self
+            .recent
+            .iter()
+            .skip(split)
+            .chain(self.ahead.iter().take_while(|f| **f != b'\n'))
+            .copied()
+            .collect();
+        let before: Vec<char> = String::from_utf8_lossy(&before).chars().collect();
+        let after: Vec<char> = String::from_utf8_lossy(&after).chars().collect();
+
+        let from = before.len().saturating_sub(EXCERPT_RADIUS);
+        let mut text: String = before[from..]
+            .iter()
+            .chain(after.iter().take(EXCERPT_RADIUS))
+            .collect();
+        if from > 0 || self.recent_cut {
+            text.insert_str(0, "...");
+        }
+        if after.len() > EXCERPT_RADIUS {
+            text.push_str("...");
+        }
+
+        Ok(Excerpt {
+            line: self.line,
+            column,
+            text,
+        })
+    }
+}
+
+/// Whether the rest of two texts have the same tokens, after the tokens given beforehand.
+fn same_rest_tokens(
+    answer: &mut Cursor,
+    output: &mut Cursor,
+    answer_tokens: &str,
+    output_tokens: &str,
+) -> io::Result<bool> {
+    let mut answer_tokens = answer_tokens.split_ascii_whitespace();
+    let mut output_tokens = output_tokens.split_ascii_whitespace();
+    loop {
+        let expected = match answer_tokens.next() {
+            Some(token) => Some(token.as_bytes().to_vec()),
+            None => answer.next_token()?.map(|f| f.2),
+        };
+        let found = match output_tokens.next() {
+            Some(token) => Some(token.as_bytes().to_vec()),
+            None => output.next_token()?.map(|f| f.2),
+        };
+        if expected != found {
+            return Ok(false);
+        }
+        if expected.is_none() {
+            return Ok(true);
+        }
+    }
+}
+
+/// The first pair of tokens which do not match, if there is.
+fn token_difference<F>(
+    answer: &mut dyn BufRead,
+    output: &mut dyn BufRead,
+    matches: F,
+) -> io::Result<Option<Difference>>
+where
+    F: Fn(&[u8], &[u8]) -> bool,
+{
+    let (mut answer, mut output) = (Cursor::new(answer), Cursor::new(output));
+    let mut index = 0;
+    loop {
+        index += 1;
+        let (expected, found) = (answer.next_token()?, output.next_token()?);
+        match (&expected, &found) {
+            (None, None) => return Ok(None),
+            (Some(expected), Some(found)) if matches(&expected.2, &found.2) => continue,
+            _ => {}
+        }
+
+        let excerpt = |token: (usize, usize, Vec<u8>)| Excerpt {
+            column: token.1,
+            ..excerpt_of_line(&String::from_utf8_lossy(&token.2), token.0, 1)
+        };
+        return Ok(Some(Difference {
+            token: Some(index),
+            expected: expected.map(excerpt),
+            found: found.map(excerpt),
+        }));
+    }
+}
+
+fn token_result(difference: Option<Difference>) -> ComparisionResult {
+    match difference {
+        None => ComparisionResult::Same,
+        Some(difference) => ComparisionResult::Different(difference),
+    }
+}
+
+/// Reads lines between the first and the last non-blank ones, with their line numbers and trailing blanks trimmed.
+struct Lines<'a> {
+    cursor: Cursor<'a>,
+    /// blank lines read but not given yet, as the number of the first one and how many they are.
+    blanks: Option<(usize, usize)>,
+    /// a non-blank line read after blank lines, which are given first.
+    held: Option<(usize, String)>,
+    started: bool,
+}
+
+impl<'a> Lines<'a> {
+    fn new(cursor: Cursor<'a>) -> Self {
+        Self {
+            cursor,
+            blanks: None,
+            held: None,
+            started: false,
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<(usize, String)>> {
+        loop {
+            // blank lines only count when a non-blank one follows
+            if self.held.is_some() {
+                if let Some((line, count)) = self.blanks.take() {
+                    if count > 1 {
+                        self.blanks = Some((line + 1, count - 1));
+                    }
+                    return Ok(Some((line, String::new())));
+                }
+                return Ok(self.held.take());
+            }
+
+            let line = self.cursor.line;
+            let text = match self.cursor.next_line()? {
+                Some(text) => text.trim_end().to_string(),
+                None => return Ok(None),
+            };
+            if !text.is_empty() {
+                self.started = true;
+                self.held = Some((line, text));
+            } else if self.started {
+                self.blanks = match self.blanks {
+                    Some((first, count)) => Some((first, count + 1)),
+                    None => Some((line, 1)),
+                };
+            }
+        }
+    }
+
+    /// Text read but not given yet.
+    fn held_text(&self) -> &str {
+        self.held.as_ref().map_or("", |f| f.1.as_str())
+    }
+
+    fn collect(mut self) -> io::Result<Vec<(usize, String)>> {
+        let mut lines = Vec::new();
+        while let Some(line) = self.next_line()? {
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+}
+
+/// Difference between two lines at the same position, whole lines shown when `column` is not given.
+fn line_difference(
+    line1: Option<&(usize, String)>,
+    line2: Option<&(usize, String)>,
+    column: Option<usize>,
+) -> Difference {
+    let column = column.unwrap_or(1);
+    Difference {
+        token: None,
+        expected: line1.map(|f| excerpt_of_line(&f.1, f.0, column)),
+        found: line2.map(|f| excerpt_of_line(&f.1, f.0, column)),
+    }
 }
 
 pub struct GlobalCompare;
 
 impl ComparisionMode for GlobalCompare {
-    fn compare(&self,str1: &String, str2: &String) -> ComparisionResult {
-        let value_res=ValueCompare{}.strict_compare(str1, str2);
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        let (mut answer, mut output) = (Cursor::new(answer), Cursor::new(output));
+        // whether the same bytes so far end in the middle of a token
+        let mut in_token = false;
+        loop {
+            let (a, b) = (answer.peek()?, output.peek()?);
+            if a == b {
+                match a {
+                    None => return Ok(ComparisionResult::Same),
+                    Some(byte) => in_token = !byte.is_ascii_whitespace(),
+                }
+                answer.bump()?;
+                output.bump()?;
+                continue;
+            }
 
-        if str1 == str2 {
-            ComparisionResult::Same
-        } else {
-            value_res
+            let difference = Difference {
+                token: None,
+                expected: if a.is_some() { Some(answer.excerpt()?) } else { None },
+                found: if b.is_some() { Some(output.excerpt()?) } else { None },
+            };
+
+            // rest of the token cut by the difference should be the same too
+            let same_tokens = if in_token {
+                answer.rest_of_token()? == output.rest_of_token()?
+            } else {
+                true
+            };
+            return Ok(
+                if same_tokens && same_rest_tokens(&mut answer, &mut output, "", "")? {
+                    ComparisionResult::PatternDifferent(difference)
+                } else {
+                    ComparisionResult::Different(difference)
+                },
+            );
         }
     }
 }
@@ -37,53 +492,502 @@ impl ComparisionMode for GlobalCompare {
 pub struct LineCompare;
 
 impl ComparisionMode for LineCompare {
-    fn compare(&self,str1: &String, str2: &String) -> ComparisionResult {
-        let value_res=ValueCompare{}.strict_compare(str1, str2);
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        // the whole text is trimmed, including blanks at the beginning of its first line
+        let (mut answer, mut output) = (Cursor::new(answer), Cursor::new(output));
+        answer.skip_whitespace()?;
+        output.skip_whitespace()?;
+        let (mut lines1, mut lines2) = (Lines::new(answer), Lines::new(output));
+
+        loop {
+            let (line1, line2) = (lines1.next_line()?, lines2.next_line()?);
+            if line1.as_ref().map(|f| &f.1) == line2.as_ref().map(|f| &f.1) {
+                if line1.is_none() {
+                    return Ok(ComparisionResult::Same);
+                }
+                continue;
+            }
 
-        let str1 = str1.trim();
-        let str2 = str2.trim();
-        let str1: Vec<&str> = str1.split("\n").map(|f| f.trim_end()).collect();
-        let str2: Vec<&str> = str2.split("\n").map(|f| f.trim_end()).collect();
+            // column where the two lines begin to differ
+            let column = match (&line1, &line2) {
+                (Some(line1), Some(line2)) => {
+                    line1
+                        .1
+                        .chars()
+                        .zip(line2.1.chars())
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                        + 1
+                }
+                _ => 1,
+            };
+            let difference = line_difference(line1.as_ref(), line2.as_ref(), Some(column));
 
-        if str1.len() != str2.len() {
-            return value_res;
+            let answer_tokens = format!(
+                "{} {}",
+                line1.as_ref().map_or("", |f| f.1.as_str()),
+                lines1.held_text()
+            );
+            let output_tokens = format!(
+                "{} {}",
+                line2.as_ref().map_or("", |f| f.1.as_str()),
+                lines2.held_text()
+            );
+            return Ok(
+                if same_rest_tokens(
+                    &mut lines1.cursor,
+                    &mut lines2.cursor,
+                    &answer_tokens,
+                    &output_tokens,
+                )? {
+                    ComparisionResult::PatternDifferent(difference)
+                } else {
+                    ComparisionResult::Different(difference)
+                },
+            );
         }
+    }
+}
 
-        for pair in str1.into_iter().zip(str2.into_iter()) {
-            if pair.0 != pair.1 {
-                return value_res;
-            }
+/// The first line of `lines` which is left over after taking out those in `others`, regardless of order.
+fn first_unmatched_line<'a>(
+    lines: &'a [(usize, String)],
+    others: &[(usize, String)],
+) -> Option<&'a (usize, String)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, line) in others {
+        *counts.entry(line).or_default() += 1;
+    }
+    for line in lines {
+        match counts.get_mut(line.1.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => return Some(line),
         }
+    }
+    None
+}
+
+/// Output must have the same lines as answer, in any order.
+pub struct UnorderedLinesCompare;
+
+impl ComparisionMode for UnorderedLinesCompare {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        let lines1 = Lines::new(Cursor::new(answer)).collect()?;
+        let lines2 = Lines::new(Cursor::new(output)).collect()?;
 
-        return ComparisionResult::Same;
+        let missing = first_unmatched_line(&lines1, &lines2);
+        let extra = first_unmatched_line(&lines2, &lines1);
+        if missing.is_none() && extra.is_none() {
+            return Ok(ComparisionResult::Same);
+        }
+
+        Ok(ComparisionResult::Different(line_difference(
+            missing, extra, None,
+        )))
     }
 }
 
-pub struct ValueCompare;
+/// Output must have the same lines as answer in order, while tokens of each line may be in any order.
+pub struct UnorderedTokensCompare;
 
-impl ValueCompare{
-    fn strict_compare(&self,str1:&String,str2:&String)->ComparisionResult{
-        if str1==str2{
-            return ComparisionResult::Same;
-        }else{
-            match self.compare(str1, str2) {
-                ComparisionResult::Same => ComparisionResult::PatternDifferent,
-                ComparisionResult::Different => ComparisionResult::Different,
-                _ => unreachable!(),
+impl ComparisionMode for UnorderedTokensCompare {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        let mut lines1 = Lines::new(Cursor::new(answer));
+        let mut lines2 = Lines::new(Cursor::new(output));
+
+        loop {
+            let (line1, line2) = (lines1.next_line()?, lines2.next_line()?);
+            match (&line1, &line2) {
+                (None, None) => return Ok(ComparisionResult::Same),
+                (Some(line1), Some(line2)) => {
+                    let mut tokens1: Vec<&str> = line1.1.split_ascii_whitespace().collect();
+                    let mut tokens2: Vec<&str> = line2.1.split_ascii_whitespace().collect();
+                    tokens1.sort_unstable();
+                    tokens2.sort_unstable();
+                    if tokens1 == tokens2 {
+                        continue;
+                    }
+                }
+                _ => {}
             }
+
+            return Ok(ComparisionResult::Different(line_difference(
+                line1.as_ref(),
+                line2.as_ref(),
+                None,
+            )));
         }
     }
 }
 
+pub struct ValueCompare;
+
 impl ComparisionMode for ValueCompare {
-    fn compare(&self,str1: &String, str2: &String) -> ComparisionResult {
-        let str1 = str1.replace("\n", "").replace(" ", "");
-        let str2 = str2.replace("\n", "").replace(" ", "");
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        Ok(token_result(token_difference(answer, output, |a, b| {
+            a == b
+        })?))
+    }
+}
 
-        if str1==str2{
-            ComparisionResult::Same
-        }else{
-            ComparisionResult::Different
+/// Compare tokens like `ValueCompare`, regardless of their case.
+pub struct CaseInsensitiveValueCompare;
+
+impl ComparisionMode for CaseInsensitiveValueCompare {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        Ok(token_result(token_difference(answer, output, |a, b| {
+            String::from_utf8_lossy(a).to_lowercase() == String::from_utf8_lossy(b).to_lowercase()
+        })?))
+    }
+}
+
+/// Compile a line of answer into a regex matching a whole line.
+pub fn line_pattern(pattern: &str) -> std::result::Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// Each line of output must match the regex in the same line of answer.
+///
+/// Lines are picked out as in `LineCompare`. An invalid regex matches nothing.
+pub struct RegexCompare;
+
+impl ComparisionMode for RegexCompare {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        let mut lines1 = Lines::new(Cursor::new(answer));
+        let mut lines2 = Lines::new(Cursor::new(output));
+
+        loop {
+            let (line1, line2) = (lines1.next_line()?, lines2.next_line()?);
+            match (&line1, &line2) {
+                (None, None) => return Ok(ComparisionResult::Same),
+                (Some(line1), Some(line2)) => {
+                    if let Ok(pattern) = line_pattern(&line1.1) {
+                        if pattern.is_match(&line2.1) {
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            return Ok(ComparisionResult::Different(line_difference(
+                line1.as_ref(),
+                line2.as_ref(),
+                None,
+            )));
+        }
+    }
+}
+
+/// Reads a text with `\r\n` turned into `\n`.
+struct CrlfToLf<'a> {
+    reader: &'a mut dyn BufRead,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<'a> CrlfToLf<'a> {
+    fn new(reader: &'a mut dyn BufRead) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            pos: 0,
         }
     }
-}
\ No newline at end of file
+}
+
+impl<'a> Read for CrlfToLf<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let buf = self.fill_buf()?;
+        let len = buf.len().min(out.len());
+        out[..len].copy_from_slice(&buf[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<'a> BufRead for CrlfToLf<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // a chunk of `\r` alone may turn into nothing, so read until there is something
+        while self.pos >= self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+
+            let chunk = self.reader.fill_buf()?;
+            let len = chunk.len();
+            if len == 0 {
+                break;
+            }
+            for (index, &byte) in chunk.iter().enumerate() {
+                if byte == b'\r' && chunk.get(index + 1) == Some(&b'\n') {
+                    continue;
+                }
+                self.buf.push(byte);
+            }
+            let cr_at_end = chunk[len - 1] == b'\r';
+            self.reader.consume(len);
+
+            // `\r\n` split by the end of chunk
+            if cr_at_end && self.reader.fill_buf()?.first() == Some(&b'\n') {
+                self.buf.pop();
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+/// Treat `\r\n` as `\n` in both texts, and then compare them by the inner mode.
+pub struct NewlineNormalized<C: ComparisionMode>(pub C);
+
+impl<C: ComparisionMode> ComparisionMode for NewlineNormalized<C> {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        self.0
+            .compare_stream(&mut CrlfToLf::new(answer), &mut CrlfToLf::new(output))
+    }
+}
+
+/// Compare tokens, where numbers are the same within absolute or relative error.
+pub struct FloatCompare {
+    pub abs_eps: f64,
+    pub rel_eps: f64,
+}
+
+impl FloatCompare {
+    fn token_matches(&self, expected: &[u8], found: &[u8]) -> bool {
+        if expected == found {
+            return true;
+        }
+        let parse = |token: &[u8]| std::str::from_utf8(token).ok()?.parse::<f64>().ok();
+        match (parse(expected), parse(found)) {
+            // nan and inf are only the same as they are written
+            (Some(a), Some(b)) if a.is_finite() && b.is_finite() => {
+                let diff = (a - b).abs();
+                diff <= self.abs_eps || diff <= self.rel_eps * a.abs()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ComparisionMode for FloatCompare {
+    fn compare_stream(
+        &self,
+        answer: &mut dyn BufRead,
+        output: &mut dyn BufRead,
+    ) -> io::Result<ComparisionResult> {
+        Ok(token_result(token_difference(answer, output, |a, b| {
+            self.token_matches(a, b)
+        })?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+    #[test]
+    fn value_by_tokens() {
+        let compare = ValueCompare {};
+        assert!(matches!(
+            compare.compare(&"1 23".into(), &"12 3".into()),
+            ComparisionResult::Different(_)
+        ));
+        assert!(matches!(
+            compare.compare(&"1 2\n3".into(), &"1\t2\r\n 3 \n".into()),
+            ComparisionResult::Same
+        ));
+    }
+
+    #[test]
+    fn newline_normalized() {
+        let answer = "1 2\n3\n".to_string();
+        let output = "1 2\r\n3\r\n".to_string();
+        assert!(matches!(
+            GlobalCompare {}.compare(&answer, &output),
+            ComparisionResult::PatternDifferent(_)
+        ));
+        assert!(matches!(
+            NewlineNormalized(GlobalCompare {}).compare(&answer, &output),
+            ComparisionResult::Same
+        ));
+    }
+
+    #[test]
+    fn float_within_eps() {
+        let compare = FloatCompare {
+            abs_eps: 1e-6,
+            rel_eps: 1e-6,
+        };
+        let answer = "3.1415926 1000000\nyes".to_string();
+        assert!(matches!(
+            compare.compare(&answer, &"3.1415927  1000000.5 yes\n".into()),
+            ComparisionResult::Same
+        ));
+
+        let result = compare.compare(&answer, &"3.1415926 1000002 yes".into());
+        let difference = result.difference().unwrap();
+        assert_eq!(difference.token, Some(2));
+        assert_eq!(difference.found.as_ref().unwrap().text, "1000002");
+    }
+
+    #[test]
+    fn float_strings_exact() {
+        let compare = FloatCompare {
+            abs_eps: 1e-6,
+            rel_eps: 0.0,
+        };
+        let result = compare.compare(&"1.0 YES".into(), &"1.0 yes".into());
+        assert_eq!(result.difference().unwrap().token, Some(2));
+
+        let result = compare.compare(&"1.0 2.0".into(), &"1.0".into());
+        let difference = result.difference().unwrap();
+        assert_eq!(difference.expected.as_ref().unwrap().text, "2.0");
+        assert!(difference.found.is_none());
+
+        assert!(matches!(
+            compare.compare(&"nan".into(), &"nan".into()),
+            ComparisionResult::Same
+        ));
+        assert!(matches!(
+            compare.compare(&"nan".into(), &"1.0".into()),
+            ComparisionResult::Different(_)
+        ));
+    }
+
+    #[test]
+    fn difference_located() {
+        let answer = "1 2 3\n4 5 6\n".to_string();
+        let output = "1 2 3\n4 7 6\n".to_string();
+
+        let result = LineCompare {}.compare(&answer, &output);
+        let found = result.difference().unwrap().found.as_ref().unwrap();
+        assert_eq!((found.line, found.column), (2, 3));
+        assert_eq!(found.text, "4 7 6");
+
+        let result = GlobalCompare {}.compare(&answer, &"1 2 3\n4 5 6".into());
+        let difference = result.difference().unwrap();
+        assert!(difference.found.is_none());
+        assert_eq!(difference.expected.as_ref().unwrap().line, 2);
+
+        let result = ValueCompare {}.compare(&answer, &"1 2 3 4\n7".into());
+        let difference = result.difference().unwrap();
+        assert_eq!(difference.token, Some(5));
+        let found = difference.found.as_ref().unwrap();
+        assert_eq!((found.line, found.column), (2, 1));
+    }
+
+    #[test]
+    fn unordered_lines() {
+        let answer = "1 2\n3 4\n3 4\n".to_string();
+        assert!(matches!(
+            UnorderedLinesCompare {}.compare(&answer, &"\n3 4\n1 2  \n3 4".into()),
+            ComparisionResult::Same
+        ));
+
+        let result = UnorderedLinesCompare {}.compare(&answer, &"3 4\n1 2\n4 3\n".into());
+        let difference = result.difference().unwrap();
+        assert_eq!(difference.expected.as_ref().unwrap().line, 3);
+        assert_eq!(difference.found.as_ref().unwrap().text, "4 3");
+    }
+
+    #[test]
+    fn unordered_tokens() {
+        let answer = "1 2 3\n4 5\n".to_string();
+        assert!(matches!(
+            UnorderedTokensCompare {}.compare(&answer, &"3  1 2\n5 4".into()),
+            ComparisionResult::Same
+        ));
+        assert!(matches!(
+            UnorderedTokensCompare {}.compare(&answer, &"1 2\n3 4 5".into()),
+            ComparisionResult::Different(_)
+        ));
+
+        let result = UnorderedTokensCompare {}.compare(&answer, &"1 2 3".into());
+        assert!(result.difference().unwrap().found.is_none());
+    }
+
+    #[test]
+    fn value_ignore_case() {
+        let compare = CaseInsensitiveValueCompare {};
+        assert!(matches!(
+            compare.compare(&"YES 1\nNo".into(), &"yes 1 nO".into()),
+            ComparisionResult::Same
+        ));
+        assert!(matches!(
+            compare.compare(&"YES".into(), &"yes!".into()),
+            ComparisionResult::Different(_)
+        ));
+    }
+
+    #[test]
+    fn regex_lines() {
+        let answer = "\\d+ (apple|banana)s?\nDone\\.\n".to_string();
+        assert!(matches!(
+            RegexCompare {}.compare(&answer, &"12 apples  \nDone.".into()),
+            ComparisionResult::Same
+        ));
+
+        // a pattern must match the whole line
+        let result = RegexCompare {}.compare(&answer, &"12 apples\nDone. ok".into());
+        assert_eq!(result.difference().unwrap().found.as_ref().unwrap().line, 2);
+
+        assert!(matches!(
+            RegexCompare {}.compare(&"(".into(), &"(".into()),
+            ComparisionResult::Different(_)
+        ));
+    }
+
+    #[test]
+    fn stream_in_small_chunks() -> io::Result<()> {
+        // `\r\n` and chars of several bytes are split by chunks of a byte
+        let answer = "1 2\né\n\n";
+        let output = "1 2\r\nè \r\n";
+        let mut answer = BufReader::with_capacity(1, answer.as_bytes());
+        let mut output = BufReader::with_capacity(1, output.as_bytes());
+        let result = NewlineNormalized(LineCompare {}).compare_stream(&mut answer, &mut output)?;
+
+        let difference = result.difference().unwrap();
+        let found = difference.found.as_ref().unwrap();
+        assert_eq!((found.line, found.column), (2, 1));
+        assert_eq!(found.text, "è");
+
+        let result = GlobalCompare {}.compare(&"1 é".into(), &"1 è".into());
+        let found = result.difference().unwrap().found.as_ref().unwrap().clone();
+        assert_eq!((found.line, found.column), (1, 3));
+        assert_eq!(found.text, "1 è");
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    size_of, CompileResult, CompiledProgram, CompilerEnvironmentStatus, CompilerOptions, Language,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+/// Size of the default compile cache, in bytes.
+pub const DEFAULT_CACHE_SIZE: u64 = 1 << 30;
+
+/// Compiled programs kept on disk, keyed by the SHA-256 of source, grader, compiler and options,
+/// so that judging a submission again reuses what was built before.
+///
+/// Each program is kept in a directory of its own, which is a copy of where it was built.
+/// The least recently used ones are removed once all of them are larger than the size of cache.
+pub struct CompileCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Default for CompileCache {
+    fn default() -> Self {
+        Self::with_size(DEFAULT_CACHE_SIZE)
+    }
+}
+
+impl CompileCache {
+    /// A cache in `dir`, holding `max_size` bytes at most.
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// A cache shared by all judges on the machine, in the temporary directory of system.
+    pub fn with_size(max_size: u64) -> Self {
+        Self::new(
+            std::env::temp_dir().join("iron_tank").join("compiled"),
+            max_size,
+        )
+    }
+
+    /// Compile a source in `language`, or take what was compiled from the same one.
+    /// Only programs compiled successfully are kept.
+    pub fn compile(
+        &self,
+        language: &Language,
+        options: &CompilerOptions,
+        src: String,
+        grader: Option<&GraderConfig>,
+    ) -> Result<CompileResult> {
+        let entry = self.dir.join(self.key(language, options, &src, grader)?);
+        if let Some(program) = read_entry(&entry) {
+            return Ok(CompileResult::OK(program));
+        }
+
+        let compiler = language.compiler_with(options)?;
+        let program = match grader {
+            Some(grader) => compiler.compile_with_grader(src, grader),
+            None => compiler.compile(src),
+        };
+        let program = match program {
+            CompileResult::OK(program) => program,
+            failed => return Ok(failed),
+        };
+
+        let program = self.store(&entry, compiler.build_dir(), program)?;
+        self.evict(&entry)?;
+        Ok(CompileResult::OK(program))
+    }
+
+    fn key(
+        &self,
+        language: &Language,
+        options: &CompilerOptions,
+        src: &str,
+        grader: Option<&GraderConfig>,
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        // every field is prefixed by its length, so that no two keys are made of the same bytes
+        let mut update = |field: &[u8]| {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field);
+        };
+        update(language.id.as_bytes());
+        match language.check_environment() {
+            CompilerEnvironmentStatus::OK { version, path } => {
+                update(version.as_bytes());
+                update(path.as_bytes());
+            }
+            CompilerEnvironmentStatus::Missing => {
+                return Err(Error::Environment(format!(
+                    "missing toolchain of {}",
+                    language.id
+                )))
+            }
+        }
+        update(format!("{:?}", options.standard).as_bytes());
+        update(format!("{:?}", options.backend).as_bytes());
+        update(format!("{:?}", options.flags).as_bytes());
+        // a program built under a larger limit may not be built under a smaller one
+        update(format!("{:?}", options.limit).as_bytes());
+        update(src.as_bytes());
+        if let Some(grader) = grader {
+            update(format!("{} {}", grader.sources.len(), grader.headers.len()).as_bytes());
+            for file in grader.sources.iter().chain(grader.headers.iter()) {
+                update(Path::new(file).file_name().unwrap_or_default().to_string_lossy().as_bytes());
+                update(&fs::read(file)?);
+            }
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Copy what is built into `entry`, through a temporary directory so that others never see a partial one.
+    fn store(
+        &self,
+        entry: &Path,
+        built: &Path,
+        program: CompiledProgram,
+    ) -> Result<CompiledProgram> {
+        let temp_entry = entry.with_extension(format!("tmp{}", std::process::id()));
+        if temp_entry.exists() {
+            fs::remove_dir_all(&temp_entry)?;
+        }
+        copy_dir(built, &temp_entry)?;
+
+        let from = built.to_string_lossy().to_string();
+        let to = entry.to_string_lossy().to_string();
+        let program = CompiledProgram {
+            path: program.path.replace(&from, &to),
+            args: program.args.iter().map(|f| f.replace(&from, &to)).collect(),
+            ..program
+        };
+        let description = serde_yaml::to_string(&program)
+            .map_err(|f| Error::Data(format!("invalid compiled program: {}", f)))?;
+        fs::write(temp_entry.join(PROGRAM_FILE), description)?;
+
+        // another one may have stored the same program in the meantime
+        if fs::rename(&temp_entry, entry).is_err() {
+            fs::remove_dir_all(&temp_entry)?;
+        }
+        fs::write(entry.join(USED_FILE), "")?;
+        Ok(program)
+    }
+
+    /// Remove the least recently used programs until the cache fits its size, except the one in `kept`.
+    fn evict(&self, kept: &Path) -> Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let used = match fs::metadata(path.join(USED_FILE)).and_then(|f| f.modified()) {
+                Ok(used) => used,
+                // still being stored
+                Err(_) => continue,
+            };
+            entries.push((used, size_of(&path), path));
+        }
+
+        let mut total: u64 = entries.iter().map(|f| f.1).sum();
+        entries.sort();
+        for (_, size, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if path != kept {
+                fs::remove_dir_all(&path)?;
+                total -= size;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Description of the program kept in an entry.
+const PROGRAM_FILE: &str = "program.yaml";
+/// File touched whenever an entry is used, whose time tells the least recently used one.
+const USED_FILE: &str = "used";
+
+fn read_entry(entry: &Path) -> Option<CompiledProgram> {
+    let description = fs::read_to_string(entry.join(PROGRAM_FILE)).ok()?;
+    let program = serde_yaml::from_str(&description).ok()?;
+    fs::write(entry.join(USED_FILE), "").ok()?;
+    Some(program)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::{CompileLimit, CompilerRegistry};
+
+    #[test]
+    fn compile_cached() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let cache = CompileCache::new(dir.path().to_path_buf(), DEFAULT_CACHE_SIZE);
+        let registry = CompilerRegistry::default();
+        let language = registry.by_language("cpp").unwrap();
+        let options = CompilerOptions::default();
+        let src = "#include <cstdio>\nint main(){puts(\"hi\");}";
+
+        let first = match cache.compile(language, &options, src.into(), None)? {
+            CompileResult::OK(program) => program,
+            _ => panic!("failed to compile"),
+        };
+        assert!(first
+            .path
+            .starts_with(&dir.path().to_string_lossy().to_string()));
+        assert!(Path::new(&first.path).exists());
+        let second = match cache.compile(language, &options, src.into(), None)? {
+            CompileResult::OK(program) => program,
+            _ => panic!("failed to compile"),
+        };
+        assert_eq!(first.path, second.path);
+
+        // other flags build another program
+        let options = CompilerOptions {
+            flags: vec!["-DONLINE_JUDGE".into()],
+            ..options
+        };
+        let flagged = match cache.compile(language, &options, src.into(), None)? {
+            CompileResult::OK(program) => program,
+            _ => panic!("failed to compile"),
+        };
+        assert_ne!(flagged.path, first.path);
+        // so does another limit
+        let limited = CompilerOptions {
+            limit: Some(CompileLimit {
+                time_limit: 5000,
+                ..CompileLimit::default()
+            }),
+            ..options.clone()
+        };
+        match cache.compile(language, &limited, src.into(), None)? {
+            CompileResult::OK(program) => assert_ne!(program.path, flagged.path),
+            _ => panic!("failed to compile"),
+        }
+        assert!(matches!(
+            cache.compile(language, &options, "int main(){".into(), None)?,
+            CompileResult::CompileError(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn compile_cache_evicted() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        // smaller than any executable, so that only the latest one is kept
+        let cache = CompileCache::new(dir.path().to_path_buf(), 1);
+        let registry = CompilerRegistry::default();
+        let language = registry.by_language("cpp").unwrap();
+        let options = CompilerOptions::default();
+
+        for src in &["int main(){return 0;}", "int main(){return 1;}"] {
+            assert!(matches!(
+                cache.compile(language, &options, src.to_string(), None)?,
+                CompileResult::OK(_)
+            ));
+        }
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+
+        Ok(())
+    }
+}
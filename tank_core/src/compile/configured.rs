@@ -0,0 +1,250 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    compiler_command, copy_grader_files, run_compiler, CompileDiagnostics, CompileLimit,
+    CompileResult, CompiledProgram, Compiler, CompilerEnvironmentStatus, RunProfile,
+    DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+/// A file of languages, loaded by `CompilerRegistry::load_languages`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LanguagesConfig {
+    pub languages: Vec<LanguageConfig>,
+}
+
+/// A language defined without writing a compiler for it.
+///
+/// In commands, `{src}` is the path of source, `{exec}` is where the executable should be,
+/// `{dir}` is the directory of them, and an argument `{sources}` is replaced by all sources built together.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LanguageConfig {
+    pub id: String,
+    pub suffixes: Vec<String>,
+    /// command printing the version of toolchain, like `go version`.
+    pub detect: Vec<String>,
+    /// regex finding the version in what `detect` prints, the first group of which is taken if there is one.
+    #[serde(rename = "versionRegex")]
+    pub version_regex: String,
+    /// name of the saved source, `src.<first suffix>` by default.
+    pub source: Option<String>,
+    /// command building the source, which is run under the compile limit. Sources are run directly if it is not given.
+    pub compile: Option<Vec<String>>,
+    pub run: Vec<String>,
+    /// permissions of the cell running the program.
+    #[serde(default = "default_permissions")]
+    pub permissions: Vec<String>,
+    #[serde(rename = "timeMultiplier", default = "default_multiplier")]
+    pub time_multiplier: f64,
+    #[serde(rename = "memoryMultiplier", default = "default_multiplier")]
+    pub memory_multiplier: f64,
+}
+
+fn default_permissions() -> Vec<String> {
+    vec!["minimum".into()]
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+impl LanguageConfig {
+    pub fn check_environment(&self) -> CompilerEnvironmentStatus {
+        let path = match self.detect.first().map(which::which) {
+            Some(Ok(path)) => path,
+            _ => return CompilerEnvironmentStatus::Missing,
+        };
+        let output = match Command::new(&path).args(&self.detect[1..]).output() {
+            Ok(output) => output,
+            Err(_) => return CompilerEnvironmentStatus::Missing,
+        };
+        // some toolchains, like java, print their version to stderr
+        let printed = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let version = Regex::new(&self.version_regex)
+            .ok()
+            .and_then(|f| f.captures(&printed))
+            .and_then(|f| f.get(1).or_else(|| f.get(0)))
+            .map(|f| f.as_str().to_string());
+
+        match version {
+            Some(version) => CompilerEnvironmentStatus::OK {
+                version,
+                path: path.to_string_lossy().to_string(),
+            },
+            None => CompilerEnvironmentStatus::Missing,
+        }
+    }
+
+    fn source_name(&self) -> String {
+        self.source
+            .clone()
+            .unwrap_or_else(|| format!("src.{}", self.suffixes.first().map_or("", |f| f.as_str())))
+    }
+}
+
+pub struct ConfiguredCompiler {
+    temp_dir: tempfile::TempDir,
+    config: LanguageConfig,
+    version: String,
+    limit: CompileLimit,
+}
+
+impl ConfiguredCompiler {
+    pub fn new(config: LanguageConfig) -> Result<Self> {
+        match config.check_environment() {
+            CompilerEnvironmentStatus::OK { version, path: _ } => Ok(Self {
+                temp_dir: tempfile::TempDir::new()?,
+                config,
+                version,
+                limit: DEFAULT_COMPILE_LIMIT,
+            }),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment(format!(
+                "missing toolchain of {}",
+                config.id
+            ))),
+        }
+    }
+
+    fn write_source(&self, src: String) -> PathBuf {
+        let code_path = self.temp_dir.path().join(self.config.source_name());
+
+        {
+            let mut file = File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    /// Fill a command template, resolving the program of it from `PATH`.
+    fn expand(&self, template: &[String], sources: &[PathBuf]) -> Result<(String, Vec<String>)> {
+        let dir = self.temp_dir.path();
+        let exec = dir.join("exec");
+        let fill = |f: &String| {
+            f.replace("{src}", &sources[0].to_string_lossy())
+                .replace("{exec}", &exec.to_string_lossy())
+                .replace("{dir}", &dir.to_string_lossy())
+        };
+
+        let mut args = Vec::new();
+        for arg in template {
+            if arg == "{sources}" {
+                args.extend(sources.iter().map(|f| f.to_string_lossy().to_string()));
+            } else {
+                args.push(fill(arg));
+            }
+        }
+        if args.is_empty() {
+            return Err(Error::Argument(format!(
+                "empty command of {}",
+                self.config.id
+            )));
+        }
+
+        let program = args.remove(0);
+        let program = if Path::new(&program).is_absolute() {
+            program
+        } else {
+            which::which(&program)
+                .map_err(|_| Error::Environment(format!("missing `{}`", program)))?
+                .to_string_lossy()
+                .to_string()
+        };
+        Ok((program, args))
+    }
+
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        if let Some(template) = &self.config.compile {
+            let (compiler, args) = match self.expand(template, &sources) {
+                Ok(command) => command,
+                Err(err) => return self.failed(err),
+            };
+
+            let mut command = compiler_command(&compiler, &self.limit, RunProfile::Native);
+            command.args(args).current_dir(self.temp_dir.path());
+
+            let exec_path = self.temp_dir.path().join("exec");
+            if let Err(result) = run_compiler(
+                command,
+                &self.version,
+                self.temp_dir.path(),
+                &self.limit,
+                &exec_path,
+            ) {
+                return result;
+            }
+        }
+
+        match self.expand(&self.config.run, &sources) {
+            Ok((path, args)) => CompileResult::OK(CompiledProgram {
+                path,
+                args,
+                permissions: self.config.permissions.clone(),
+                time_multiplier: self.config.time_multiplier,
+                memory_multiplier: self.config.memory_multiplier,
+                profile: RunProfile::Native,
+            }),
+            Err(err) => self.failed(err),
+        }
+    }
+
+    fn failed(&self, err: Error) -> CompileResult {
+        CompileResult::CompileError(CompileDiagnostics::new(
+            &format!("{:?}", err),
+            &self.version,
+        ))
+    }
+}
+
+impl Compiler for ConfiguredCompiler {
+    fn compile(&self, src: String) -> CompileResult {
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        if let Err(result) = copy_grader_files(&grader.headers, self.temp_dir.path(), &self.version) {
+            return result;
+        }
+        let mut sources = vec![code_path];
+        match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(grader_sources) => sources.extend(grader_sources),
+            Err(result) => return result,
+        }
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+/// Read languages defined in a YAML file.
+pub fn read_languages(path: &str) -> Result<Vec<LanguageConfig>> {
+    let content = fs::read_to_string(path)?;
+    let config: LanguagesConfig = serde_yaml::from_str(&content)
+        .map_err(|f| Error::Argument(format!("invalid languages `{}`: {}", path, f)))?;
+    Ok(config.languages)
+}
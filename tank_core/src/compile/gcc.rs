@@ -0,0 +1,170 @@
+use std::{path::{Path, PathBuf}, str::FromStr};
+use std::fs::File;
+use std::io::{Write};
+
+use crate::{error::{Error,Result}, problem::GraderConfig};
+
+use super::{compiler_command, copy_grader_files, environment_of, run_compiler, CompileLimit, CompileResult, CompiledProgram, Compiler, CompilerBackend, CompilerDescriptor, CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT};
+
+pub struct CompilerGCC {
+    temp_dir: tempfile::TempDir,
+    standard: GCCStandard,
+    compiler_path:String,
+    version: String,
+    flags: Vec<String>,
+    limit: CompileLimit,
+}
+
+#[derive(Debug,Clone)]
+pub enum GCCStandard {
+    C99,
+    C11,
+    C17,
+}
+
+impl From<GCCStandard> for String{
+    fn from(v: GCCStandard) -> Self {
+        match v {
+            GCCStandard::C99 => "-std=c99".into(),
+            GCCStandard::C11 => "-std=c11".into(),
+            GCCStandard::C17 => "-std=c17".into(),
+        }
+    }
+}
+
+impl FromStr for GCCStandard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "c99" => Ok(GCCStandard::C99),
+            "c11" => Ok(GCCStandard::C11),
+            "c17" => Ok(GCCStandard::C17),
+            _ => Err(Error::Argument(format!("unknown standard of C: {}", s))),
+        }
+    }
+}
+
+impl CompilerDescriptor for CompilerGCC{
+    fn support_sufix()->Vec<&'static str> {
+        vec!["c"]
+    }
+    fn check_environment() -> CompilerEnvironmentStatus {
+        environment_of(CompilerBackend::GCC.c_compiler())
+    }
+}
+
+impl Compiler for CompilerGCC{
+    fn compile(&self, src: String) ->CompileResult{
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        if let Err(result) = copy_grader_files(&grader.headers, self.temp_dir.path(), &self.version) {
+            return result;
+        }
+        let mut sources = vec![code_path];
+        match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(grader_sources) => sources.extend(grader_sources),
+            Err(result) => return result,
+        }
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerGCC {
+    fn write_source(&self, src: String) -> PathBuf {
+        let code_path = self.temp_dir.path().join("src.c");
+
+        {
+            let mut file=File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    /// Compile and link all `sources` into one executable.
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        let exec_path = self.temp_dir.path().join("exec");
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::Native);
+        command
+            .args(&sources)
+            .arg("-o")
+            .arg(exec_path.to_str().unwrap())
+            .arg(String::from(self.standard.clone()))
+            .arg("-O2")
+            .args(&self.flags)
+            // libm is not linked by default, while `math.h` is used a lot
+            .arg("-lm");
+
+        match run_compiler(command, &self.version, self.temp_dir.path(), &self.limit, &exec_path){
+            Ok(())=>CompileResult::OK(CompiledProgram::new(
+                exec_path.to_str().unwrap().to_string()
+            )),
+            Err(result)=>result,
+        }
+    }
+
+    pub fn new() -> Result<Self> {
+        Self::with_options(GCCStandard::C17, CompilerBackend::GCC, Vec::new())
+    }
+
+    /// Compile by `backend` in `standard`, with extra `flags` after the default ones.
+    pub fn with_options(standard: GCCStandard, backend: CompilerBackend, flags: Vec<String>) -> Result<Self> {
+        match environment_of(backend.c_compiler()){
+            CompilerEnvironmentStatus::OK { version, path } => {
+                Ok(Self {
+                    temp_dir: tempfile::TempDir::new().unwrap(),
+                    standard,
+                    compiler_path:path,
+                    version,
+                    flags,
+                    limit: DEFAULT_COMPILE_LIMIT,
+                })
+            }
+            CompilerEnvironmentStatus::Missing => {
+                Err(Error::Environment(format!("missing {}", backend.c_compiler())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcc_compile_error()->Result<()>{
+        let src="#include <stdio.h>\nint main(){printf(\"hi\\n\");}asd";
+        let compiler=CompilerGCC::new()?;
+        assert!(matches!(compiler.compile(src.into()),CompileResult::CompileError(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn gcc_compile_ok()->Result<()>{
+        let src="#include <stdio.h>\n#include <math.h>\nint main(){double x;scanf(\"%lf\",&x);printf(\"%f\\n\",sqrt(x));}";
+        let compiler=CompilerGCC::new()?;
+        assert!(matches!(compiler.compile(src.into()),CompileResult::OK(_)));
+
+        assert!(matches!("C11".parse::<GCCStandard>(),Ok(GCCStandard::C11)));
+        assert!(matches!("c++17".parse::<GCCStandard>(),Err(_)));
+
+        Ok(())
+    }
+}
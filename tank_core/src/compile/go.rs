@@ -0,0 +1,201 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use regex::Regex;
+
+use super::{
+    compiler_command, copy_grader_files, run_compiler, CompileLimit, CompileResult,
+    CompiledProgram, Compiler, CompilerDescriptor, CompilerEnvironmentStatus, RunProfile,
+    DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+pub struct CompilerGo {
+    temp_dir: tempfile::TempDir,
+    compiler_path: String,
+    version: String,
+    flags: Vec<String>,
+    limit: CompileLimit,
+}
+
+impl CompilerDescriptor for CompilerGo {
+    fn support_sufix() -> Vec<&'static str> {
+        vec!["go"]
+    }
+
+    fn check_environment() -> CompilerEnvironmentStatus {
+        // go has no `--version`, it prints like `go version go1.17.5 linux/amd64`
+        let path = match which::which("go") {
+            Ok(path) => path,
+            Err(_) => return CompilerEnvironmentStatus::Missing,
+        };
+        let output = match Command::new(&path).arg("version").output() {
+            Ok(output) => output,
+            Err(_) => return CompilerEnvironmentStatus::Missing,
+        };
+        let printed = String::from_utf8_lossy(&output.stdout);
+        match Regex::new(r"go(\d+(\.\d+)*)").unwrap().captures(&printed) {
+            Some(version) => CompilerEnvironmentStatus::OK {
+                version: version[1].to_string(),
+                path: path.to_string_lossy().to_string(),
+            },
+            None => CompilerEnvironmentStatus::Missing,
+        }
+    }
+}
+
+impl Compiler for CompilerGo {
+    fn compile(&self, src: String) -> CompileResult {
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    /// Sources of grader are built in the same `main` package as the submission.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        let mut sources = vec![code_path];
+        match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(grader_sources) => sources.extend(grader_sources),
+            Err(result) => return result,
+        }
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerGo {
+    pub fn new() -> Result<Self> {
+        Self::with_options(Vec::new())
+    }
+
+    /// Compile with extra `flags` of `go build`, like `-gcflags=-B`.
+    pub fn with_options(flags: Vec<String>) -> Result<Self> {
+        match Self::check_environment() {
+            CompilerEnvironmentStatus::OK { version, path } => Ok(Self {
+                temp_dir: tempfile::TempDir::new().unwrap(),
+                compiler_path: path,
+                version,
+                flags,
+                limit: DEFAULT_COMPILE_LIMIT,
+            }),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment("missing go".into())),
+        }
+    }
+
+    fn write_source(&self, src: String) -> PathBuf {
+        let code_path = self.temp_dir.path().join("src.go");
+
+        {
+            let mut file = File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        let exec_path = self.temp_dir.path().join("exec");
+        // the build cache is shared by submissions, so that the standard library is built once
+        let cache_dir = std::env::temp_dir().join("iron_tank").join("go");
+        fs::create_dir_all(&cache_dir).unwrap();
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::Native);
+        command
+            .arg("build")
+            .arg("-o")
+            .arg(&exec_path)
+            .args(&self.flags)
+            .args(&sources)
+            // nothing is downloaded, neither modules nor another toolchain
+            .env("GOPROXY", "off")
+            .env("GOFLAGS", "-mod=mod")
+            .env("GOTOOLCHAIN", "local")
+            .env("GO111MODULE", "on")
+            .env("GOPATH", cache_dir.join("path"))
+            .env("GOCACHE", cache_dir.join("build"))
+            .current_dir(self.temp_dir.path());
+
+        if let Err(result) = run_compiler(
+            command,
+            &self.version,
+            self.temp_dir.path(),
+            &self.limit,
+            &exec_path,
+        ) {
+            return result;
+        }
+
+        CompileResult::OK(CompiledProgram {
+            // the runtime always starts threads for scheduler and garbage collector
+            permissions: vec!["minimum".into(), "go".into()],
+            ..CompiledProgram::new(exec_path.to_string_lossy().to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn go_environment() {
+        match CompilerGo::check_environment() {
+            CompilerEnvironmentStatus::OK { version, path: _ } => {
+                assert!(version.starts_with('1'));
+            }
+            CompilerEnvironmentStatus::Missing => {
+                assert!(matches!(CompilerGo::new(), Err(Error::Environment(_))));
+            }
+        }
+    }
+
+    #[test]
+    fn go_compile() -> Result<()> {
+        // go is not installed everywhere, which is covered by `go_environment`
+        if let CompilerEnvironmentStatus::Missing = CompilerGo::check_environment() {
+            return Ok(());
+        }
+
+        let compiler = CompilerGo::new()?;
+        let src = "package main\n\nimport \"fmt\"\n\nfunc main() { fmt.Println(1) }";
+        match compiler.compile(src.into()) {
+            CompileResult::OK(program) => {
+                assert!(Path::new(&program.path).exists());
+                assert!(program.permissions.contains(&"go".to_string()));
+            }
+            _ => panic!("failed to compile"),
+        }
+        assert!(matches!(
+            compiler.compile("package main\n\nfunc main() { x := 1 }".into()),
+            CompileResult::CompileError(_)
+        ));
+
+        // modules are never downloaded, so a third-party import fails at once
+        let src = "package main\n\nimport \"github.com/google/uuid\"\n\nfunc main() { _ = uuid.New() }";
+        match compiler.compile(src.into()) {
+            CompileResult::CompileError(diagnostics) => {
+                assert!(diagnostics.stderr.contains("github.com/google/uuid"));
+                assert_eq!(diagnostics.status, Some(1));
+            }
+            _ => panic!("compiled a third-party import offline"),
+        }
+
+        Ok(())
+    }
+}
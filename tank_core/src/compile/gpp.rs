@@ -1,28 +1,53 @@
-use std::{io::Read, process::{Command, Stdio}};
+use std::{path::{Path, PathBuf}, str::FromStr};
 use std::fs::File;
 use std::io::{Write};
 
-use crate::{error::{Error,Result}, judge::get_path_of_tankcell, probe::ProcessProbe};
+use crate::{error::{Error,Result}, problem::GraderConfig};
 
-use super::{CompileResult, CompiledProgram, Compiler, CompilerDescriptor, CompilerEnvironmentStatus};
+use super::{compiler_command, copy_grader_files, environment_of, run_compiler, CompileLimit, CompileResult, CompiledProgram, Compiler, CompilerBackend, CompilerDescriptor, CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT};
 
 pub struct CompilerGPP {
     temp_dir: tempfile::TempDir,
     standard: GPPStandard,
     compiler_path:String,
+    version: String,
+    flags: Vec<String>,
+    limit: CompileLimit,
 }
 
 #[derive(Debug,Clone)]
 pub enum GPPStandard {
     CPP11,
+    CPP14,
     CPP17,
+    CPP20,
+    CPP23,
 }
 
 impl From<GPPStandard> for String{
     fn from(v: GPPStandard) -> Self {
         match v {
             GPPStandard::CPP11 => "-std=c++11".into(),
+            GPPStandard::CPP14 => "-std=c++14".into(),
             GPPStandard::CPP17 => "-std=c++17".into(),
+            GPPStandard::CPP20 => "-std=c++20".into(),
+            // older compilers only know the draft name
+            GPPStandard::CPP23 => "-std=c++2b".into(),
+        }
+    }
+}
+
+impl FromStr for GPPStandard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "c++11" => Ok(GPPStandard::CPP11),
+            "c++14" => Ok(GPPStandard::CPP14),
+            "c++17" => Ok(GPPStandard::CPP17),
+            "c++20" => Ok(GPPStandard::CPP20),
+            "c++23" => Ok(GPPStandard::CPP23),
+            _ => Err(Error::Argument(format!("unknown standard of C++: {}", s))),
         }
     }
 }
@@ -33,31 +58,43 @@ impl CompilerDescriptor for CompilerGPP{
     }
     fn check_environment() -> CompilerEnvironmentStatus {
         // TODO: check whether chosen standard is supported or not
-        let path = which::which("g++");
-        match path {
-            Ok(path) => {
-                let output = Command::new(&path)
-                    .arg("--version")
-                    .stdout(Stdio::piped())
-                    .output()
-                    .unwrap();
-                let stdout = String::from_utf8(output.stdout).unwrap();
-                let version = stdout.lines().next().unwrap().split(" ").last().unwrap();
-
-                CompilerEnvironmentStatus::OK {
-                    version: version.into(),
-                    path: path.to_string_lossy().into(),
-                }
-            }
-            Err(_) => CompilerEnvironmentStatus::Missing,
-        }
+        environment_of(CompilerBackend::GCC.cpp_compiler())
     }
 }
 
 impl Compiler for CompilerGPP{
     fn compile(&self, src: String) ->CompileResult{
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        // headers only need to be found by `#include`
+        if let Err(result) = copy_grader_files(&grader.headers, self.temp_dir.path(), &self.version) {
+            return result;
+        }
+        let mut sources = vec![code_path];
+        match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(grader_sources) => sources.extend(grader_sources),
+            Err(result) => return result,
+        }
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerGPP {
+    fn write_source(&self, src: String) -> PathBuf {
         let code_path = self.temp_dir.path().join("src.cpp");
-        let exec_path = self.temp_dir.path().join("exec");
 
         {
             let mut file=File::create(&code_path).unwrap();
@@ -65,60 +102,49 @@ impl Compiler for CompilerGPP{
             file.sync_all().unwrap();
         }
 
-        // println!("{} {} {} {}",get_path_of_tankcell(),&self.compiler_path,"-p full",format!(
-        //     "-- {} -o {} {}",
-        //     code_path.to_str().unwrap(),
-        //     exec_path.to_str().unwrap(),
-        //     String::from(self.standard),
-        // ));
-
-        let command = Command::new(get_path_of_tankcell())
-            .arg(&self.compiler_path)
-            .arg("-p")
-            .arg("full")
-            .arg("--")
-            .arg(code_path.to_str().unwrap())
+        code_path
+    }
+
+    /// Compile and link all `sources` into one executable.
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        let exec_path = self.temp_dir.path().join("exec");
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::Native);
+        command
+            .args(&sources)
             .arg("-o")
             .arg(exec_path.to_str().unwrap())
             .arg(String::from(self.standard.clone()))
             .arg("-O2")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
-        let probe=ProcessProbe::new(command.id()).unwrap();
-        let probe=probe.watching();
-
-        let mut stdout=String::new();
-        command.stdout.unwrap().read_to_string(&mut stdout).unwrap();
-        let mut stderr=String::new();
-        command.stderr.unwrap().read_to_string(&mut stderr).unwrap();
-
-        // TODO: handle limit
-
-        if probe.get_status()!=0{
-            CompileResult::CompileError
-        }else{
-            CompileResult::OK(CompiledProgram::new(
+            .args(&self.flags);
+
+        match run_compiler(command, &self.version, self.temp_dir.path(), &self.limit, &exec_path){
+            Ok(())=>CompileResult::OK(CompiledProgram::new(
                 exec_path.to_str().unwrap().to_string()
-            ))
+            )),
+            Err(result)=>result,
         }
-    }   
-}
+    }
 
-impl CompilerGPP {
     pub fn new() -> Result<Self> {
-        // TODO: expose standard config
-        match Self::check_environment(){
-            CompilerEnvironmentStatus::OK { version: _, path } => {
+        Self::with_options(GPPStandard::CPP17, CompilerBackend::GCC, Vec::new())
+    }
+
+    /// Compile by `backend` in `standard`, with extra `flags` after the default ones.
+    pub fn with_options(standard: GPPStandard, backend: CompilerBackend, flags: Vec<String>) -> Result<Self> {
+        match environment_of(backend.cpp_compiler()){
+            CompilerEnvironmentStatus::OK { version, path } => {
                 Ok(Self {
                     temp_dir: tempfile::TempDir::new().unwrap(),
-                    standard: GPPStandard::CPP17,
+                    standard,
                     compiler_path:path,
+                    version,
+                    flags,
+                    limit: DEFAULT_COMPILE_LIMIT,
                 })
             }
             CompilerEnvironmentStatus::Missing => {
-                Err(Error::Environment("missing g++".into()))
+                Err(Error::Environment(format!("missing {}", backend.cpp_compiler())))
             }
         }
     }
@@ -144,7 +170,23 @@ mod tests {
     fn gpp_compile_error()->Result<()>{
         let src="#include <iostream> \n int main(){std::cout<<\"hi\"<<std::endl;}asd";
         let compiler=CompilerGPP::new()?;
-        assert!(matches!(compiler.compile(src.into()),CompileResult::CompileError));
+        assert!(matches!(compiler.compile(src.into()),CompileResult::CompileError(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn gpp_diagnostics()->Result<()>{
+        let compiler=CompilerGPP::new()?;
+        match compiler.compile("int main(){return undefined_name;}".into()){
+            CompileResult::CompileError(diagnostics)=>{
+                assert!(diagnostics.stderr.contains("undefined_name"));
+                assert!(diagnostics.stderr.starts_with("src.cpp"));
+                assert_eq!(diagnostics.status,Some(1));
+                assert!(!diagnostics.version.is_empty());
+            }
+            _=>panic!("compiled a wrong source"),
+        }
 
         Ok(())
     }
@@ -157,4 +199,57 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn gpp_missing_grader()->Result<()>{
+        let compiler=CompilerGPP::new()?;
+        let grader=GraderConfig{sources:vec!["../test_dep/missing/grader.cpp".into()],headers:Vec::new()};
+        match compiler.compile_with_grader("int main(){}".into(),&grader){
+            CompileResult::CompileError(diagnostics)=>{
+                assert!(diagnostics.stderr.contains("grader.cpp"));
+                assert_eq!(diagnostics.status,None);
+            }
+            _=>panic!("compiled without grader"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn gpp_limit()->Result<()>{
+        let mut compiler=CompilerGPP::new()?;
+        compiler.set_limit(CompileLimit{time_limit:2000,memory_limit:256,output_limit:16});
+        let bombs=[
+            // read until memory runs out
+            "#include \"/dev/zero\"\nint main(){}",
+            // instances of template grow exponentially
+            "template<int D,int I>struct F{static const int v=F<D-1,2*I>::v+F<D-1,2*I+1>::v;};\ntemplate<int I>struct F<0,I>{static const int v=I;};\nint main(){return F<24,0>::v;}",
+        ];
+        for src in bombs{
+            assert!(matches!(compiler.compile(src.into()),CompileResult::LimitExceeded),"{}",src);
+        }
+        // an initialized array is written into the object, and `as` is killed by the size limit
+        // while g++ removes the object
+        assert!(matches!(compiler.compile("char a[32<<20]={1};\nint main(){return a[1];}".into()),CompileResult::LimitExceeded));
+        // what a source prints is never taken as a limit
+        assert!(matches!(compiler.compile("#error \"virtual memory exhausted\"".into()),CompileResult::CompileError(_)));
+        assert!(matches!(compiler.compile("int main(){}".into()),CompileResult::OK(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn gpp_standards()->Result<()>{
+        // generic lambdas are only in C++14 and later
+        let src="int main(){auto f=[](auto x){return x;};return f(0);}";
+        let compiler=CompilerGPP::with_options(GPPStandard::CPP11, CompilerBackend::GCC, Vec::new())?;
+        assert!(matches!(compiler.compile(src.into()),CompileResult::CompileError(_)));
+        let compiler=CompilerGPP::with_options(GPPStandard::CPP14, CompilerBackend::GCC, vec!["-DONLINE_JUDGE".into()])?;
+        assert!(matches!(compiler.compile(src.into()),CompileResult::OK(_)));
+
+        assert!(matches!("C++20".parse::<GPPStandard>(),Ok(GPPStandard::CPP20)));
+        assert!(matches!("c++98".parse::<GPPStandard>(),Err(_)));
+
+        Ok(())
+    }
+}
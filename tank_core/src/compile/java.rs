@@ -0,0 +1,264 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+
+use super::{
+    compiler_command, copy_grader_files, environment_of, run_compiler, CompileDiagnostics,
+    CompileLimit, CompileResult, CompiledProgram, Compiler, CompilerDescriptor,
+    CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+pub struct CompilerJava {
+    temp_dir: tempfile::TempDir,
+    compiler_path: String,
+    version: String,
+    java_path: String,
+    limit: CompileLimit,
+}
+
+impl CompilerDescriptor for CompilerJava {
+    fn support_sufix() -> Vec<&'static str> {
+        vec!["java"]
+    }
+
+    fn check_environment() -> CompilerEnvironmentStatus {
+        match environment_of("java") {
+            CompilerEnvironmentStatus::OK { .. } => environment_of("javac"),
+            CompilerEnvironmentStatus::Missing => CompilerEnvironmentStatus::Missing,
+        }
+    }
+}
+
+impl Compiler for CompilerJava {
+    fn compile(&self, src: String) -> CompileResult {
+        let main_class = detect_main_class(&src);
+        let code_path = self.write_source(src);
+        self.build(vec![code_path], main_class)
+    }
+
+    /// The main class is found in the first source of grader.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        let mut sources = vec![code_path];
+        let grader_sources =
+            match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+                Ok(grader_sources) => grader_sources,
+                Err(result) => return result,
+            };
+        let main_class = match grader_sources.first().map(fs::read_to_string) {
+            Some(Ok(entry)) => detect_main_class(&entry),
+            _ => {
+                return CompileResult::CompileError(CompileDiagnostics::new(
+                    "grader has no source",
+                    &self.version,
+                ))
+            }
+        };
+        sources.extend(grader_sources);
+
+        self.build(sources, main_class)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerJava {
+    pub fn new() -> Result<Self> {
+        let java_path = match environment_of("java") {
+            CompilerEnvironmentStatus::OK { version: _, path } => path,
+            CompilerEnvironmentStatus::Missing => {
+                return Err(Error::Environment("missing java".into()))
+            }
+        };
+        match Self::check_environment() {
+            CompilerEnvironmentStatus::OK { version, path } => Ok(Self {
+                temp_dir: tempfile::TempDir::new().unwrap(),
+                compiler_path: path,
+                version,
+                java_path,
+                limit: DEFAULT_COMPILE_LIMIT,
+            }),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment("missing javac".into())),
+        }
+    }
+
+    /// Save the source in a file named by its public class, as javac requires.
+    fn write_source(&self, src: String) -> PathBuf {
+        let name = public_class(&src).unwrap_or_else(|| "Main".into());
+        let code_path = self.temp_dir.path().join(format!("{}.java", name));
+
+        {
+            let mut file = File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    fn build(&self, sources: Vec<PathBuf>, main_class: String) -> CompileResult {
+        let class_path = self.temp_dir.path().join("classes");
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::JVM);
+        command
+            // heap of javac itself
+            .arg(format!("-J-Xmx{}m", self.limit.memory_limit))
+            .arg("-encoding")
+            .arg("UTF-8")
+            .arg("-d")
+            .arg(&class_path)
+            .args(&sources);
+
+        if let Err(result) = run_compiler(
+            command,
+            &self.version,
+            self.temp_dir.path(),
+            &self.limit,
+            &class_path,
+        ) {
+            return result;
+        }
+
+        CompileResult::OK(jvm_program(
+            self.java_path.clone(),
+            vec![
+                "-cp".into(),
+                class_path.to_string_lossy().to_string(),
+                main_class,
+            ],
+        ))
+    }
+}
+
+/// A program run by `java` with the JVM profile.
+pub(super) fn jvm_program(java_path: String, args: Vec<String>) -> CompiledProgram {
+    let mut jvm_args: Vec<String> = vec![
+        // one thread of GC, and nothing written to `/tmp`
+        "-XX:+UseSerialGC".into(),
+        "-XX:-UsePerfData".into(),
+    ];
+    jvm_args.extend(args);
+
+    CompiledProgram {
+        permissions: vec!["minimum".into(), "jvm".into()],
+        profile: RunProfile::JVM,
+        ..CompiledProgram::new_with_args(java_path, jvm_args)
+    }
+}
+
+fn public_class(src: &str) -> Option<String> {
+    let public = Regex::new(r"public\s+(?:(?:final|abstract)\s+)*class\s+(\w+)").unwrap();
+    public.captures(src).map(|f| f[1].to_string())
+}
+
+/// Full name of the class declaring `main`, or of the public class if `main` is not found.
+pub fn detect_main_class(src: &str) -> String {
+    let package = Regex::new(r"(?m)^\s*package\s+([\w.]+)\s*;").unwrap();
+    let main = Regex::new(r"static\s+(?:final\s+)?void\s+main\s*\(").unwrap();
+    let class = Regex::new(r"\bclass\s+(\w+)").unwrap();
+
+    let name = main
+        .find(src)
+        .and_then(|main| {
+            // classes whose bodies are open where `main` is, from the outermost one
+            let mut opened: Vec<Option<String>> = Vec::new();
+            let mut declared = class.captures_iter(&src[..main.start()]).peekable();
+            let mut pending = None;
+            for (i, c) in src[..main.start()].char_indices() {
+                while let Some(f) = declared.peek() {
+                    if f.get(0).unwrap().start() > i {
+                        break;
+                    }
+                    pending = Some(f[1].to_string());
+                    declared.next();
+                }
+                match c {
+                    '{' => opened.push(pending.take()),
+                    '}' => {
+                        opened.pop();
+                    }
+                    _ => {}
+                }
+            }
+            let names: Vec<String> = opened.into_iter().flatten().collect();
+            if names.is_empty() {
+                None
+            } else {
+                Some(names.join("$"))
+            }
+        })
+        .or_else(|| public_class(src))
+        .unwrap_or_else(|| "Main".into());
+
+    match package.captures(src) {
+        Some(package) => format!("{}.{}", &package[1], name),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::LimitConfig;
+
+    #[test]
+    fn main_class() {
+        assert_eq!(
+            detect_main_class(
+                "public final class Solution { public static void main(String[] a){} }"
+            ),
+            "Solution"
+        );
+        assert_eq!(
+            detect_main_class("package a.b;\nclass Pair {}\nclass Main { static class Inner {}\n static void main(String[] a){} }"),
+            "a.b.Main"
+        );
+        assert_eq!(
+            detect_main_class("class Pair {}\nclass Outer { static class Solver { public static void main(String[] a){} } }"),
+            "Outer$Solver"
+        );
+    }
+
+    #[test]
+    fn java_compile() -> Result<()> {
+        let compiler = CompilerJava::new()?;
+        let src = "public class Solution { public static void main(String[] a){ System.out.println(1); } }";
+        match compiler.compile(src.into()) {
+            CompileResult::OK(program) => {
+                assert_eq!(program.args.last().unwrap(), "Solution");
+                let limit = LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                };
+                let args = program.run_args(&limit);
+                assert_eq!(args[0], "-Xmx256m");
+                assert_eq!(args[1], "-Xss64m");
+                assert!(program
+                    .cell_args()
+                    .contains(&"--no_address_limit".to_string()));
+            }
+            _ => panic!("failed to compile"),
+        }
+
+        assert!(matches!(
+            compiler.compile("public class Main { void f() { int x = } }".into()),
+            CompileResult::CompileError(_)
+        ));
+
+        Ok(())
+    }
+}
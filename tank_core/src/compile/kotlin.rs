@@ -0,0 +1,153 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use regex::Regex;
+
+use super::{
+    compiler_command, copy_grader_files, environment_of, java::jvm_program, run_compiler,
+    CompileDiagnostics, CompileLimit, CompileResult, Compiler, CompilerDescriptor,
+    CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+pub struct CompilerKotlin {
+    temp_dir: tempfile::TempDir,
+    compiler_path: String,
+    version: String,
+    java_path: String,
+    limit: CompileLimit,
+}
+
+impl CompilerDescriptor for CompilerKotlin {
+    fn support_sufix() -> Vec<&'static str> {
+        vec!["kt"]
+    }
+
+    fn check_environment() -> CompilerEnvironmentStatus {
+        let path = match which::which("kotlinc") {
+            Ok(path) => path,
+            Err(_) => return CompilerEnvironmentStatus::Missing,
+        };
+        if let CompilerEnvironmentStatus::Missing = environment_of("java") {
+            return CompilerEnvironmentStatus::Missing;
+        }
+        // kotlinc prints like `info: kotlinc-jvm 1.6.10 (JRE 17)` to stderr
+        let output = match Command::new(&path).arg("-version").output() {
+            Ok(output) => output,
+            Err(_) => return CompilerEnvironmentStatus::Missing,
+        };
+        let printed = String::from_utf8_lossy(&output.stderr);
+        match Regex::new(r"kotlinc-jvm (\S+)").unwrap().captures(&printed) {
+            Some(version) => CompilerEnvironmentStatus::OK {
+                version: version[1].to_string(),
+                path: path.to_string_lossy().to_string(),
+            },
+            None => CompilerEnvironmentStatus::Missing,
+        }
+    }
+}
+
+impl Compiler for CompilerKotlin {
+    fn compile(&self, src: String) -> CompileResult {
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    /// Sources of grader are built into the same jar, one of which should have the only `main`.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        let mut sources = vec![code_path];
+        match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(grader_sources) => sources.extend(grader_sources),
+            Err(result) => return result,
+        }
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerKotlin {
+    pub fn new() -> Result<Self> {
+        let java_path = match environment_of("java") {
+            CompilerEnvironmentStatus::OK { version: _, path } => path,
+            CompilerEnvironmentStatus::Missing => {
+                return Err(Error::Environment("missing java".into()))
+            }
+        };
+        match Self::check_environment() {
+            CompilerEnvironmentStatus::OK { version, path } => Ok(Self {
+                temp_dir: tempfile::TempDir::new().unwrap(),
+                compiler_path: path,
+                version,
+                java_path,
+                // kotlinc spends seconds of CPU warming up before compiling anything
+                limit: CompileLimit {
+                    time_limit: 30000,
+                    ..DEFAULT_COMPILE_LIMIT
+                },
+            }),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment("missing kotlinc".into())),
+        }
+    }
+
+    fn write_source(&self, src: String) -> PathBuf {
+        let code_path = self.temp_dir.path().join("src.kt");
+
+        {
+            let mut file = File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    /// Build a jar with kotlin runtime, whose manifest tells the main class.
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        let jar_path = self.temp_dir.path().join("exec.jar");
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::JVM);
+        command
+            .arg(format!("-J-Xmx{}m", self.limit.memory_limit))
+            .args(&sources)
+            .arg("-include-runtime")
+            .arg("-d")
+            .arg(&jar_path);
+
+        if let Err(result) = run_compiler(
+            command,
+            &self.version,
+            self.temp_dir.path(),
+            &self.limit,
+            &jar_path,
+        ) {
+            return result;
+        }
+        if !jar_path.exists() {
+            return CompileResult::CompileError(CompileDiagnostics::new(
+                "no jar is built",
+                &self.version,
+            ));
+        }
+
+        CompileResult::OK(jvm_program(
+            self.java_path.clone(),
+            vec!["-jar".into(), jar_path.to_string_lossy().to_string()],
+        ))
+    }
+}
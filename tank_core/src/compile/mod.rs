@@ -1,7 +1,47 @@
-use crate::JudgeStatus;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::mpsc::channel,
+    thread,
+    time::Duration,
+};
 
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    judge::{get_path_of_tankcell, read_in_background},
+    probe::ProcessProbe,
+    problem::{GraderConfig, LimitConfig},
+    JudgeStatus,
+};
+
+use self::{
+    cache::CompileCache,
+    configured::{read_languages, ConfiguredCompiler},
+    gcc::{CompilerGCC, GCCStandard},
+    go::CompilerGo,
+    gpp::{CompilerGPP, GPPStandard},
+    java::CompilerJava,
+    kotlin::CompilerKotlin,
+    python::{CompilerPyPy, CompilerPython, PythonInterpreter},
+    rustc::{CompilerRustc, RustEdition},
+};
+
+pub mod cache;
+pub mod configured;
+pub mod gcc;
+pub mod go;
 pub mod gpp;
+pub mod java;
+pub mod kotlin;
 pub mod python;
+pub mod rustc;
 
 pub trait CompilerDescriptor {
     fn support_sufix() -> Vec<&'static str>;
@@ -10,6 +50,13 @@ pub trait CompilerDescriptor {
 
 pub trait Compiler {
     fn compile(&self, src: String) -> CompileResult;
+    /// Replace the limit of compiling, which is `DEFAULT_COMPILE_LIMIT` of the language at first.
+    fn set_limit(&mut self, limit: CompileLimit);
+    /// Directory where sources are built, holding all that compiled programs need besides the toolchain.
+    fn build_dir(&self) -> &Path;
+    /// Build the submission together with jury grader. Sources and headers of grader are put
+    /// beside the submission, which is saved as `src` with the suffix of its language.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult;
 }
 
 #[derive(Debug)]
@@ -17,29 +64,199 @@ pub enum CompilerEnvironmentStatus {
     OK { version: String, path: String },
     Missing,
 }
-#[derive(Debug, Clone)]
+
+/// Find a toolchain in `PATH`, whose version is the first number like `1.2.3` in what `--version` prints.
+fn environment_of(name: &str) -> CompilerEnvironmentStatus {
+    let path = match which::which(name) {
+        Ok(path) => path,
+        Err(_) => return CompilerEnvironmentStatus::Missing,
+    };
+    let output = match Command::new(&path)
+        .arg("--version")
+        .stdout(Stdio::piped())
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => return CompilerEnvironmentStatus::Missing,
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = Regex::new(r"\d+(\.\d+)+")
+        .unwrap()
+        .find(stdout.lines().next().unwrap_or_default())
+        .map(|f| f.as_str())
+        .unwrap_or_default();
+
+    CompilerEnvironmentStatus::OK {
+        version: version.into(),
+        path: path.to_string_lossy().into(),
+    }
+}
+
+/// Toolchain building C and C++.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerBackend {
+    GCC,
+    Clang,
+}
+
+impl CompilerBackend {
+    pub fn c_compiler(&self) -> &'static str {
+        match self {
+            CompilerBackend::GCC => "gcc",
+            CompilerBackend::Clang => "clang",
+        }
+    }
+
+    pub fn cpp_compiler(&self) -> &'static str {
+        match self {
+            CompilerBackend::GCC => "g++",
+            CompilerBackend::Clang => "clang++",
+        }
+    }
+}
+
+impl FromStr for CompilerBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "gcc" => Ok(CompilerBackend::GCC),
+            "clang" => Ok(CompilerBackend::Clang),
+            _ => Err(Error::Argument(format!("unknown compiler backend: {}", s))),
+        }
+    }
+}
+
+/// How a submission is compiled, given in problem config or by command line.
+/// What is not given is left to the default of the language.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompilerOptions {
+    /// standard of the language, like `c++20` or `c11`, or edition of Rust like `2018`.
+    pub standard: Option<String>,
+    pub backend: Option<CompilerBackend>,
+    /// extra flags passed to the compiler.
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// limit of compiling, `DEFAULT_COMPILE_LIMIT` of the language by default.
+    pub limit: Option<CompileLimit>,
+}
+
+impl CompilerOptions {
+    /// Options of `self` overriding those of `base`. Flags of both are kept.
+    pub fn or(self, base: &CompilerOptions) -> CompilerOptions {
+        let mut flags = base.flags.clone();
+        flags.extend(self.flags);
+        CompilerOptions {
+            standard: self.standard.or_else(|| base.standard.clone()),
+            backend: self.backend.or(base.backend),
+            flags,
+            limit: self.limit.or_else(|| base.limit.clone()),
+        }
+    }
+}
+
+/// Limit of a compiler, which is exceeded by things like template bombs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompileLimit {
+    /// CPU time(ms) of the compiler and all processes it starts.
+    #[serde(rename = "timeLimit")]
+    pub time_limit: u64,
+    /// memory(MB) of the compiler.
+    #[serde(rename = "memoryLimit")]
+    pub memory_limit: u64,
+    /// size(MB) of what is built, and of each file written while building.
+    #[serde(rename = "outputLimit")]
+    pub output_limit: u64,
+}
+
+pub const DEFAULT_COMPILE_LIMIT: CompileLimit = CompileLimit {
+    time_limit: 10000,
+    memory_limit: 2048,
+    output_limit: 64,
+};
+
+impl Default for CompileLimit {
+    fn default() -> Self {
+        DEFAULT_COMPILE_LIMIT
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompiledProgram {
     pub path: String,
     pub args: Vec<String>,
+    /// permissions of the cell running it, `minimum` by default.
+    pub permissions: Vec<String>,
+    /// how many times of the limits of a problem it is given, for slow languages.
+    pub time_multiplier: f64,
+    pub memory_multiplier: f64,
+    pub profile: RunProfile,
+}
+
+/// How a program is run by tank cell, besides its permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RunProfile {
+    Native,
+    /// The program is a JVM, whose heap and stack are set by the limits,
+    /// while its address space is not limited since JVM reserves much more than it uses.
+    JVM,
 }
 
 impl CompiledProgram {
     pub fn new(path: String) -> Self {
+        Self::new_with_args(path, Vec::new())
+    }
+
+    pub fn new_with_args(path: String, args: Vec<String>) -> Self {
         Self {
             path,
-            args: Vec::new(),
+            args,
+            permissions: vec!["minimum".into()],
+            time_multiplier: 1.0,
+            memory_multiplier: 1.0,
+            profile: RunProfile::Native,
         }
     }
 
-    pub fn new_with_args(path: String, args: Vec<String>) -> Self {
-        Self { path, args }
+    /// Arguments for tank cell to grant the permissions and set up the profile.
+    pub fn cell_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = self.permissions.iter().map(|f| format!("-p {}", f)).collect();
+        if self.profile == RunProfile::JVM {
+            args.push("--no_address_limit".into());
+        }
+        args
+    }
+
+    /// Arguments of the program under `limit`.
+    pub fn run_args(&self, limit: &LimitConfig) -> Vec<String> {
+        match self.profile {
+            RunProfile::Native => self.args.clone(),
+            RunProfile::JVM => {
+                // a quarter of memory for stack is enough for deep recursion,
+                // which is common in solutions
+                let mut args = vec![
+                    format!("-Xmx{}m", limit.memory_limit),
+                    format!("-Xss{}m", (limit.memory_limit / 4).max(1)),
+                ];
+                args.extend(self.args.iter().cloned());
+                args
+            }
+        }
+    }
+
+    /// Limits of the program under limits of a problem.
+    pub fn scale_limit(&self, limit: &LimitConfig) -> LimitConfig {
+        LimitConfig {
+            time_limit: (limit.time_limit as f64 * self.time_multiplier).round() as u64,
+            memory_limit: (limit.memory_limit as f64 * self.memory_multiplier).round() as u64,
+        }
     }
 }
 #[derive(Debug)]
 pub enum CompileResult {
     OK(CompiledProgram),
     LimitExceeded,
-    CompileError, // TODO: add info
+    CompileError(CompileDiagnostics),
 }
 
 impl From<&CompileResult> for JudgeStatus {
@@ -47,7 +264,515 @@ impl From<&CompileResult> for JudgeStatus {
         match v {
             CompileResult::OK(_) => JudgeStatus::Uncertain,
             CompileResult::LimitExceeded => JudgeStatus::ComplierLimitExceeded,
-            CompileResult::CompileError => JudgeStatus::ComplierError,
+            CompileResult::CompileError(_) => JudgeStatus::ComplierError,
         }
     }
 }
+
+/// Longest output of a compiler kept in diagnostics, in bytes.
+pub const MAX_DIAGNOSTICS_LENGTH: usize = 16 * 1024;
+
+/// What is told when a source fails to compile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompileDiagnostics {
+    /// stderr of the compiler, or its stdout if nothing is in stderr, truncated to `MAX_DIAGNOSTICS_LENGTH`.
+    pub stderr: String,
+    /// exit status of the compiler, which is 128 plus the signal if it is killed,
+    /// or none if the failure is found before running it.
+    pub status: Option<i32>,
+    /// version of the compiler.
+    pub version: String,
+}
+
+impl CompileDiagnostics {
+    /// Diagnostics of a failure found by tank itself, like a grader without sources.
+    pub fn new(message: &str, version: &str) -> Self {
+        Self {
+            stderr: message.into(),
+            status: None,
+            version: version.into(),
+        }
+    }
+}
+
+impl fmt::Display for CompileDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => writeln!(f, "compiler {} exits with {}", self.version, status)?,
+            None => writeln!(f, "compiler {} is not run", self.version)?,
+        }
+        write!(f, "{}", self.stderr)
+    }
+}
+
+fn truncate_diagnostics(mut text: String) -> String {
+    if text.len() > MAX_DIAGNOSTICS_LENGTH {
+        let mut end = MAX_DIAGNOSTICS_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n... (truncated)");
+    }
+    text
+}
+
+/// Signals by which the limits of cell terminate the compiler.
+///
+/// Limits are never told from what the compiler prints, which a source can fake, like by
+/// `#error "virtual memory exhausted"`. The cell ends by the signal as well when it terminates
+/// a process started by the compiler, like `as` of g++.
+const LIMIT_SIGNALS: [libc::c_int; 3] = [libc::SIGKILL, libc::SIGXCPU, libc::SIGXFSZ];
+
+/// Command running `compiler` in tank cell under `limit`, to which arguments of the compiler are added.
+///
+/// The compiler leads a process group of its own, so that processes it starts, like `cc1plus`
+/// of g++, are killed together with it.
+fn compiler_command(compiler: &str, limit: &CompileLimit, profile: RunProfile) -> Command {
+    let mut command = Command::new(get_path_of_tankcell());
+    command
+        .arg(compiler)
+        .arg("-p")
+        .arg("full")
+        .arg(format!("-m {}", limit.memory_limit))
+        .arg(format!("-t {}", limit.time_limit))
+        .arg(format!("--file_limit={}", limit.output_limit))
+        .arg("--trace_limits");
+    if profile == RunProfile::JVM {
+        command.arg("--no_address_limit");
+    }
+    command.arg("--").stdin(Stdio::null());
+    unsafe {
+        command.pre_exec(|| {
+            libc::setpgid(0, 0);
+            Ok(())
+        });
+    }
+    command
+}
+
+fn kill_process_group(pid: u32) {
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Total size of a file, or of all files in a directory.
+fn size_of(path: &Path) -> u64 {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|entries| entries.flatten().map(|f| size_of(&f.path())).sum())
+            .unwrap_or(0),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Run a compiler made by `compiler_command`, which builds `output`.
+/// Paths under `dir`, where the source is built, are shown relatively in diagnostics.
+///
+/// CPU time is limited by the cell, but a compiler blocking forever, like one including a fifo,
+/// never hits it, so it is killed once it has lived twice as long as its time limit.
+/// Temporary files of the compiler are put in `dir`, whose size is limited like `output`.
+fn run_compiler(
+    mut command: Command,
+    version: &str,
+    dir: &Path,
+    limit: &CompileLimit,
+    output: &Path,
+) -> std::result::Result<(), CompileResult> {
+    let failed =
+        |message: String| CompileResult::CompileError(CompileDiagnostics::new(&message, version));
+    let mut child = command
+        .env("TMPDIR", dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|f| failed(format!("failed to run compiler: {}", f)))?;
+    let pid = child.id();
+    let probe = ProcessProbe::new(pid).map_err(|f| failed(f.to_string()))?;
+
+    // pipes are drained while waiting, or the compiler blocks on a long output
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let _ = sender.send(probe.watching());
+    });
+    let (probe_res, killed) =
+        match receiver.recv_timeout(Duration::from_millis(limit.time_limit * 2)) {
+            Ok(probe_res) => (probe_res, false),
+            Err(_) => {
+                kill_process_group(pid);
+                (receiver.recv().unwrap(), true)
+            }
+        };
+    // nothing started by the compiler outlives it, or the pipes are never closed
+    kill_process_group(pid);
+    let stdout = stdout.join().unwrap().unwrap_or_default();
+    let stderr = stderr.join().unwrap().unwrap_or_default();
+
+    let output_text = if stderr.is_empty() { stdout } else { stderr };
+    let output_text =
+        String::from_utf8_lossy(&output_text).replace(&format!("{}/", dir.to_string_lossy()), "");
+    let status = probe_res.get_status();
+    let status = if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else {
+        128 + libc::WTERMSIG(status)
+    };
+
+    if killed
+        || probe_res.get_time_usage() >= limit.time_limit
+        || probe_res.get_peak_memory() >= limit.memory_limit * 1024
+        || LIMIT_SIGNALS.iter().any(|f| status == 128 + f)
+        || size_of(output) >= limit.output_limit << 20
+        || size_of(dir) >= limit.output_limit << 20
+    {
+        return Err(CompileResult::LimitExceeded);
+    }
+    if status == 0 {
+        return Ok(());
+    }
+
+    Err(CompileResult::CompileError(CompileDiagnostics {
+        stderr: truncate_diagnostics(output_text),
+        status: Some(status),
+        version: version.into(),
+    }))
+}
+
+/// A language known by `CompilerRegistry`.
+pub struct Language {
+    pub id: String,
+    pub suffixes: Vec<String>,
+    check_environment: Box<dyn Fn() -> CompilerEnvironmentStatus>,
+    create: Box<dyn Fn(&CompilerOptions) -> Result<Box<dyn Compiler>>>,
+}
+
+impl Language {
+    pub fn check_environment(&self) -> CompilerEnvironmentStatus {
+        (self.check_environment)()
+    }
+
+    /// Create a compiler of this language, which fails if its environment is missing.
+    pub fn compiler(&self) -> Result<Box<dyn Compiler>> {
+        self.compiler_with(&CompilerOptions::default())
+    }
+
+    /// Create a compiler of this language with `options`, which are ignored by languages not knowing them.
+    pub fn compiler_with(&self, options: &CompilerOptions) -> Result<Box<dyn Compiler>> {
+        let mut compiler = (self.create)(options)?;
+        if let Some(limit) = &options.limit {
+            compiler.set_limit(limit.clone());
+        }
+        Ok(compiler)
+    }
+}
+
+/// Compilers known by their language ids, like `cpp` or `python`.
+pub struct CompilerRegistry {
+    languages: HashMap<String, Language>,
+}
+
+impl CompilerRegistry {
+    /// A registry without any language.
+    pub fn new() -> Self {
+        Self {
+            languages: HashMap::new(),
+        }
+    }
+
+    /// Register a compiler as language `id`, replacing the one registered before.
+    pub fn register<C: CompilerDescriptor + 'static>(
+        &mut self,
+        id: &str,
+        create: fn(&CompilerOptions) -> Result<Box<dyn Compiler>>,
+    ) {
+        self.languages.insert(
+            id.to_string(),
+            Language {
+                id: id.to_string(),
+                suffixes: C::support_sufix().into_iter().map(|f| f.to_string()).collect(),
+                check_environment: Box::new(C::check_environment),
+                create: Box::new(create),
+            },
+        );
+    }
+
+    /// Register languages defined in a YAML file, which replace those of the same ids.
+    pub fn load_languages(&mut self, path: &str) -> Result<()> {
+        for config in read_languages(path)? {
+            let detected = config.clone();
+            let created = config.clone();
+            self.languages.insert(
+                config.id.clone(),
+                Language {
+                    id: config.id,
+                    suffixes: config.suffixes,
+                    check_environment: Box::new(move || detected.check_environment()),
+                    create: Box::new(move |_| Ok(Box::new(ConfiguredCompiler::new(created.clone())?))),
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn by_language(&self, id: &str) -> Option<&Language> {
+        self.languages.get(id)
+    }
+
+    pub fn by_extension(&self, extension: &str) -> Option<&Language> {
+        let mut languages: Vec<&Language> = self.languages.values().collect();
+        // the same one is chosen every time if several languages share a suffix
+        languages.sort_by(|a, b| a.id.cmp(&b.id));
+        languages
+            .into_iter()
+            .find(|f| f.suffixes.iter().any(|f| f == extension))
+    }
+
+    /// Language of a source, given explicitly or told by its suffix.
+    pub fn resolve(&self, path: &str, language: Option<&str>) -> Result<&Language> {
+        match language {
+            Some(id) => self
+                .by_language(id)
+                .ok_or(Error::NotFound(format!("language `{}`", id))),
+            None => {
+                let extension = Path::new(path)
+                    .extension()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.by_extension(&extension)
+                    .ok_or(Error::NotFound(format!("language of `{}`", path)))
+            }
+        }
+    }
+
+    /// All languages sorted by id, with their environment.
+    pub fn available_languages(&self) -> Vec<(&Language, CompilerEnvironmentStatus)> {
+        let mut languages: Vec<(&Language, CompilerEnvironmentStatus)> = self
+            .languages
+            .values()
+            .map(|f| (f, f.check_environment()))
+            .collect();
+        languages.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+        languages
+    }
+}
+
+/// Languages supported by tank itself.
+impl Default for CompilerRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register::<CompilerGCC>("c", |options| {
+            let standard = match &options.standard {
+                Some(standard) => standard.parse()?,
+                None => GCCStandard::C17,
+            };
+            let backend = options.backend.unwrap_or(CompilerBackend::GCC);
+            Ok(Box::new(CompilerGCC::with_options(standard, backend, options.flags.clone())?))
+        });
+        registry.register::<CompilerGPP>("cpp", |options| {
+            let standard = match &options.standard {
+                Some(standard) => standard.parse()?,
+                None => GPPStandard::CPP17,
+            };
+            let backend = options.backend.unwrap_or(CompilerBackend::GCC);
+            Ok(Box::new(CompilerGPP::with_options(standard, backend, options.flags.clone())?))
+        });
+        registry.register::<CompilerGo>("go", |options| {
+            Ok(Box::new(CompilerGo::with_options(options.flags.clone())?))
+        });
+        registry.register::<CompilerJava>("java", |_| Ok(Box::new(CompilerJava::new()?)));
+        registry.register::<CompilerKotlin>("kotlin", |_| Ok(Box::new(CompilerKotlin::new()?)));
+        registry.register::<CompilerPython>("python", |_| Ok(Box::new(CompilerPython::new()?)));
+        registry.register::<CompilerPyPy>("pypy", |_| {
+            Ok(Box::new(CompilerPython::with_interpreter(PythonInterpreter::PyPy)?))
+        });
+        registry.register::<CompilerRustc>("rust", |options| {
+            let edition = match &options.standard {
+                Some(edition) => edition.parse()?,
+                None => RustEdition::E2021,
+            };
+            Ok(Box::new(CompilerRustc::with_options(edition, options.flags.clone())?))
+        });
+        registry
+    }
+}
+
+/// Prepare a jury program, checker or interactor, given by path.
+///
+/// Sources in a language of the registry are compiled through the compile cache,
+/// so that a problem is not compiling its checker for every case.
+/// Files not recognized as source are regarded as executables.
+pub fn prepare_jury_program(path: &str) -> Result<CompiledProgram> {
+    let registry = CompilerRegistry::default();
+    let language = match registry.resolve(path, None) {
+        Ok(language) => language,
+        Err(_) => return Ok(CompiledProgram::new(path.into())),
+    };
+
+    let src = fs::read_to_string(path)?;
+    match CompileCache::default().compile(language, &CompilerOptions::default(), src, None)? {
+        CompileResult::OK(program) => Ok(program),
+        CompileResult::CompileError(diagnostics) => Err(Error::UserProgram(format!(
+            "failed to compile `{}`: {}",
+            path, diagnostics
+        ))),
+        CompileResult::LimitExceeded => Err(Error::UserProgram(format!(
+            "compiler exceeds limits on `{}`",
+            path
+        ))),
+    }
+}
+
+/// Copy files of grader into the directory where the submission is built.
+/// A file which cannot be copied fails the compilation, as the grader cannot be built without it.
+fn copy_grader_files<'a, I>(
+    files: I,
+    dir: &Path,
+    version: &str,
+) -> std::result::Result<Vec<PathBuf>, CompileResult>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut copied = Vec::new();
+    for file in files {
+        let copy = |file: &String| -> Result<PathBuf> {
+            let name = Path::new(file)
+                .file_name()
+                .ok_or(Error::NotFound(file.to_string()))?;
+            let target = dir.join(name);
+            fs::copy(file, &target)?;
+            Ok(target)
+        };
+        match copy(file) {
+            Ok(target) => copied.push(target),
+            Err(err) => {
+                return Err(CompileResult::CompileError(CompileDiagnostics::new(
+                    &format!("failed to copy grader file `{}`: {:?}", file, err),
+                    version,
+                )))
+            }
+        }
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn diagnostics_truncated() {
+        let text = "错".repeat(MAX_DIAGNOSTICS_LENGTH);
+        let truncated = truncate_diagnostics(text);
+        assert!(truncated.len() <= MAX_DIAGNOSTICS_LENGTH + 20);
+        assert!(truncated.ends_with("(truncated)"));
+        assert_eq!(truncate_diagnostics("error".into()), "error");
+    }
+
+    #[test]
+    fn jury_program_cached() -> Result<()> {
+        let first = prepare_jury_program("../test_dep/checker/checker.cpp")?;
+        let second = prepare_jury_program("../test_dep/checker/checker.cpp")?;
+        assert_eq!(first.path, second.path);
+        assert!(Path::new(&first.path).exists());
+        assert!(Path::new(&first.path).starts_with(std::env::temp_dir().join("iron_tank").join("compiled")));
+
+        // python is compiled into bytecode as submissions are
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("jury.py");
+        fs::write(&path, "print(0)")?;
+        let program = prepare_jury_program(&path.to_string_lossy())?;
+        assert!(program.args[0].ends_with(".pyc"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn jury_program_executable() -> Result<()> {
+        let program = prepare_jury_program("../test_dep/checker/checker")?;
+        assert_eq!(program.path, "../test_dep/checker/checker");
+
+        Ok(())
+    }
+
+    #[test]
+    fn registry_resolve() -> Result<()> {
+        let registry = CompilerRegistry::default();
+        assert_eq!(registry.resolve("a/b.cxx", None)?.id, "cpp");
+        assert_eq!(registry.resolve("main.py", None)?.id, "python");
+        assert_eq!(registry.resolve("main.txt", Some("python"))?.id, "python");
+        assert!(matches!(
+            registry.resolve("main.txt", None),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            registry.resolve("main.cpp", Some("cobol")),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(registry.resolve("main.c", None)?.id, "c");
+        assert_eq!(registry.resolve("Main.java", None)?.id, "java");
+        assert_eq!(registry.resolve("main.rs", None)?.id, "rust");
+        assert_eq!(registry.resolve("main.py", Some("pypy"))?.id, "pypy");
+        assert_eq!(registry.available_languages().len(), 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compiler_options() -> Result<()> {
+        let registry = CompilerRegistry::default();
+        let language = registry.by_language("cpp").unwrap();
+        let options = CompilerOptions {
+            standard: Some("c++20".into()),
+            backend: None,
+            flags: vec!["-DONLINE_JUDGE".into()],
+            limit: None,
+        };
+        let src = "#ifdef ONLINE_JUDGE\nint main(){}\n#endif";
+        assert!(matches!(
+            language.compiler_with(&options)?.compile(src.into()),
+            CompileResult::OK(_)
+        ));
+        assert!(matches!(
+            language.compiler()?.compile(src.into()),
+            CompileResult::CompileError(_)
+        ));
+
+        let wrong = CompilerOptions {
+            standard: Some("c++98".into()),
+            ..options
+        };
+        assert!(matches!(
+            language.compiler_with(&wrong),
+            Err(Error::Argument(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn configured_language() -> Result<()> {
+        let mut registry = CompilerRegistry::default();
+        registry.load_languages("../test_dep/languages/languages.yaml")?;
+        let language = registry.resolve("main.sh", None)?;
+        assert!(matches!(
+            language.check_environment(),
+            CompilerEnvironmentStatus::OK { .. }
+        ));
+
+        match language.compiler()?.compile("echo hi".into()) {
+            CompileResult::OK(program) => {
+                assert!(program.args[0].ends_with("exec"));
+                assert!(Path::new(&program.args[0]).exists());
+                assert_eq!(program.time_multiplier, 2.0);
+            }
+            _ => panic!("failed to compile"),
+        }
+
+        Ok(())
+    }
+}
@@ -1,25 +1,59 @@
 use std::{
     fs::File,
     io::Write,
-    process::{Command, Stdio},
+    path::{Path, PathBuf},
 };
 
-use super::{CompileResult, CompiledProgram, Compiler, CompilerDescriptor, CompilerEnvironmentStatus};
-use crate::error::{Error, Result};
+use super::{
+    compiler_command, copy_grader_files, environment_of, run_compiler, CompileDiagnostics,
+    CompileLimit, CompileResult, CompiledProgram, Compiler, CompilerDescriptor,
+    CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
 
 pub struct CompilerPython {
     temp_dir: tempfile::TempDir,
     compiler_path: String,
+    version: String,
+    limit: CompileLimit,
+}
+
+/// Interpreter running python, which also compiles sources into bytecode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PythonInterpreter {
+    CPython,
+    PyPy,
+}
+
+impl PythonInterpreter {
+    pub fn executable(&self) -> &'static str {
+        match self {
+            PythonInterpreter::CPython => "python3",
+            PythonInterpreter::PyPy => "pypy3",
+        }
+    }
 }
 
 impl CompilerPython {
     pub fn new() -> Result<Self> {
-        match Self::check_environment() {
-            CompilerEnvironmentStatus::OK { version: _, path } => Ok(Self {
+        Self::with_interpreter(PythonInterpreter::CPython)
+    }
+
+    pub fn with_interpreter(interpreter: PythonInterpreter) -> Result<Self> {
+        match environment_of(interpreter.executable()) {
+            CompilerEnvironmentStatus::OK { version, path } => Ok(Self {
                 temp_dir: tempfile::TempDir::new().unwrap(),
                 compiler_path: path,
+                version,
+                limit: DEFAULT_COMPILE_LIMIT,
             }),
-            CompilerEnvironmentStatus::Missing => Err(Error::Environment("missing python".into())),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment(format!(
+                "missing {}",
+                interpreter.executable()
+            ))),
         }
     }
 }
@@ -30,29 +64,62 @@ impl CompilerDescriptor for CompilerPython{
     }
 
     fn check_environment() -> super::CompilerEnvironmentStatus {
-        let path = which::which("python3");
-        match path {
-            Ok(path) => {
-                let output = Command::new(&path)
-                    .arg("--version")
-                    .stdout(Stdio::piped())
-                    .output()
-                    .unwrap();
-                let stdout = String::from_utf8(output.stdout).unwrap();
-                let version = stdout.lines().next().unwrap().split(" ").last().unwrap();
-
-                CompilerEnvironmentStatus::OK {
-                    version: version.into(),
-                    path: path.to_string_lossy().to_string(),
-                }
-            }
-            Err(_) => CompilerEnvironmentStatus::Missing,
-        }
+        environment_of(PythonInterpreter::CPython.executable())
+    }
+}
+
+/// PyPy as a language of its own, which is only chosen by its id since `.py` is left to CPython.
+pub struct CompilerPyPy;
+
+impl CompilerDescriptor for CompilerPyPy {
+    fn support_sufix() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn check_environment() -> CompilerEnvironmentStatus {
+        environment_of(PythonInterpreter::PyPy.executable())
     }
 }
 
 impl Compiler for CompilerPython {
     fn compile(&self, src: String) -> super::CompileResult {
+        let code_path = self.write_source(src);
+        self.build(vec![code_path])
+    }
+
+    /// The first source of grader is run as the entry, and imports the submission as `src`.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        let code_path = self.write_source(src);
+        if let Err(result) = copy_grader_files(&grader.headers, self.temp_dir.path(), &self.version) {
+            return result;
+        }
+        let mut sources =
+            match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+                Ok(sources) => sources,
+                Err(result) => return result,
+            };
+        if sources.is_empty() {
+            return CompileResult::CompileError(CompileDiagnostics::new(
+                "grader has no source",
+                &self.version,
+            ));
+        }
+        sources.push(code_path);
+
+        self.build(sources)
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerPython {
+    fn write_source(&self, src: String) -> PathBuf {
         let code_path = self.temp_dir.path().join("src.py");
 
         {
@@ -61,9 +128,82 @@ impl Compiler for CompilerPython {
             file.sync_all().unwrap();
         }
 
+        code_path
+    }
+
+    /// Compile all `sources` into bytecode beside them, which tells syntax errors before running,
+    /// and run that of the first one.
+    fn build(&self, sources: Vec<PathBuf>) -> CompileResult {
+        let entry = sources[0].with_extension("pyc");
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::Native);
+        command
+            .arg("-m")
+            .arg("compileall")
+            .arg("-q")
+            // the compiler may be used again within the same second, which bytecode cannot tell
+            .arg("-f")
+            // `src.pyc` rather than `__pycache__/src.cpython-39.pyc`, so that the entry is found
+            // without knowing the interpreter, and modules beside it are imported by it
+            .arg("-b")
+            .args(&sources);
+
+        if let Err(result) = run_compiler(
+            command,
+            &self.version,
+            self.temp_dir.path(),
+            &self.limit,
+            &entry,
+        ) {
+            return result;
+        }
+
         CompileResult::OK(CompiledProgram::new_with_args(
             self.compiler_path.clone(),
-            vec![code_path.to_string_lossy().to_string()],
+            vec![entry.to_string_lossy().to_string()],
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn python_compile() -> Result<()> {
+        let compiler = CompilerPython::new()?;
+        match compiler.compile("print(input())".into()) {
+            CompileResult::OK(program) => {
+                assert!(program.args[0].ends_with("src.pyc"));
+                assert!(Path::new(&program.args[0]).exists());
+            }
+            _ => panic!("failed to compile"),
+        }
+
+        match compiler.compile("def f(:\n    pass".into()) {
+            CompileResult::CompileError(diagnostics) => {
+                assert!(diagnostics.stderr.contains("SyntaxError"));
+                assert!(diagnostics.stderr.contains("'src.py'"));
+                assert_eq!(diagnostics.status, Some(1));
+            }
+            _ => panic!("compiled a wrong source"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn pypy_environment() {
+        match CompilerPyPy::check_environment() {
+            CompilerEnvironmentStatus::OK { .. } => {
+                assert!(CompilerPython::with_interpreter(PythonInterpreter::PyPy).is_ok());
+            }
+            CompilerEnvironmentStatus::Missing => {
+                assert!(matches!(
+                    CompilerPython::with_interpreter(PythonInterpreter::PyPy),
+                    Err(Error::Environment(_))
+                ));
+            }
+        }
+    }
+}
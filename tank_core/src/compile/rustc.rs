@@ -0,0 +1,229 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::{
+    compiler_command, copy_grader_files, environment_of, run_compiler, CompileDiagnostics,
+    CompileLimit, CompileResult, CompiledProgram, Compiler, CompilerDescriptor,
+    CompilerEnvironmentStatus, RunProfile, DEFAULT_COMPILE_LIMIT,
+};
+use crate::{
+    error::{Error, Result},
+    problem::GraderConfig,
+};
+
+pub struct CompilerRustc {
+    temp_dir: tempfile::TempDir,
+    edition: RustEdition,
+    compiler_path: String,
+    version: String,
+    flags: Vec<String>,
+    limit: CompileLimit,
+}
+
+#[derive(Debug, Clone)]
+pub enum RustEdition {
+    E2015,
+    E2018,
+    E2021,
+}
+
+impl From<RustEdition> for String {
+    fn from(v: RustEdition) -> Self {
+        match v {
+            RustEdition::E2015 => "--edition=2015".into(),
+            RustEdition::E2018 => "--edition=2018".into(),
+            RustEdition::E2021 => "--edition=2021".into(),
+        }
+    }
+}
+
+impl FromStr for RustEdition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "2015" => Ok(RustEdition::E2015),
+            "2018" => Ok(RustEdition::E2018),
+            "2021" => Ok(RustEdition::E2021),
+            _ => Err(Error::Argument(format!("unknown edition of Rust: {}", s))),
+        }
+    }
+}
+
+impl CompilerDescriptor for CompilerRustc {
+    fn support_sufix() -> Vec<&'static str> {
+        vec!["rs"]
+    }
+
+    fn check_environment() -> CompilerEnvironmentStatus {
+        environment_of("rustc")
+    }
+}
+
+impl Compiler for CompilerRustc {
+    fn compile(&self, src: String) -> CompileResult {
+        let code_path = self.write_source(src);
+        self.build(code_path)
+    }
+
+    /// The first source of grader is the root of crate, which declares the submission by `mod src;`.
+    fn compile_with_grader(&self, src: String, grader: &GraderConfig) -> CompileResult {
+        self.write_source(src);
+        let sources = match copy_grader_files(&grader.sources, self.temp_dir.path(), &self.version) {
+            Ok(sources) => sources,
+            Err(result) => return result,
+        };
+
+        match sources.into_iter().next() {
+            Some(root) => self.build(root),
+            None => CompileResult::CompileError(CompileDiagnostics::new(
+                "grader has no source",
+                &self.version,
+            )),
+        }
+    }
+
+    fn set_limit(&mut self, limit: CompileLimit) {
+        self.limit = limit;
+    }
+
+    fn build_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
+
+impl CompilerRustc {
+    pub fn new() -> Result<Self> {
+        Self::with_options(RustEdition::E2021, Vec::new())
+    }
+
+    /// Compile in `edition`, with extra `flags`. The program is optimized by `-O`,
+    /// unless an optimization level is given in `flags`.
+    pub fn with_options(edition: RustEdition, flags: Vec<String>) -> Result<Self> {
+        match Self::check_environment() {
+            CompilerEnvironmentStatus::OK { version, path } => Ok(Self {
+                temp_dir: tempfile::TempDir::new().unwrap(),
+                edition,
+                compiler_path: path,
+                version,
+                flags,
+                limit: DEFAULT_COMPILE_LIMIT,
+            }),
+            CompilerEnvironmentStatus::Missing => Err(Error::Environment("missing rustc".into())),
+        }
+    }
+
+    fn write_source(&self, src: String) -> PathBuf {
+        let code_path = self.temp_dir.path().join("src.rs");
+
+        {
+            let mut file = File::create(&code_path).unwrap();
+            file.write_all(&src.into_bytes()).unwrap();
+            file.sync_all().unwrap();
+        }
+
+        code_path
+    }
+
+    /// Build the crate of `root`. rustc never downloads anything, so only the standard library is there.
+    fn build(&self, root: PathBuf) -> CompileResult {
+        let exec_path = self.temp_dir.path().join("exec");
+        let optimized = self.flags.iter().any(|f| f.contains("opt-level"));
+
+        let mut command = compiler_command(&self.compiler_path, &self.limit, RunProfile::Native);
+        command
+            .arg(&root)
+            .arg("-o")
+            .arg(&exec_path)
+            .arg(String::from(self.edition.clone()))
+            .args(if optimized { None } else { Some("-O") })
+            .args(&self.flags);
+
+        if let Err(result) = run_compiler(
+            command,
+            &self.version,
+            self.temp_dir.path(),
+            &self.limit,
+            &exec_path,
+        ) {
+            return result;
+        }
+
+        CompileResult::OK(CompiledProgram {
+            // std may start threads, like `std::thread::scope` in solutions
+            permissions: vec!["minimum".into(), "threads".into()],
+            ..CompiledProgram::new(exec_path.to_string_lossy().to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rustc_compile() -> Result<()> {
+        let src = "fn main() { let x: u32 = \"1\".parse().unwrap(); println!(\"{}\", x); }";
+        let compiler = CompilerRustc::new()?;
+        assert!(matches!(compiler.compile(src.into()), CompileResult::OK(_)));
+
+        let compiler = CompilerRustc::with_options(
+            RustEdition::E2021,
+            vec!["-C".into(), "opt-level=3".into()],
+        )?;
+        assert!(matches!(compiler.compile(src.into()), CompileResult::OK(_)));
+        assert!(matches!(
+            compiler.compile("fn main() { let x: u32 = \"1\"; }".into()),
+            CompileResult::CompileError(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rustc_edition() -> Result<()> {
+        // `async` is a keyword since 2018
+        let src = "fn main() { let async = 1; println!(\"{}\", async); }";
+        let compiler = CompilerRustc::with_options(RustEdition::E2015, Vec::new())?;
+        assert!(matches!(compiler.compile(src.into()), CompileResult::OK(_)));
+        let compiler = CompilerRustc::with_options("2018".parse()?, Vec::new())?;
+        assert!(matches!(
+            compiler.compile(src.into()),
+            CompileResult::CompileError(_)
+        ));
+        assert!(matches!("2024".parse::<RustEdition>(), Err(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rustc_grader() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let grader_path = dir.path().join("grader.rs");
+        std::fs::write(
+            &grader_path,
+            "mod src;\nfn main() { println!(\"{}\", src::solve(2)); }",
+        )?;
+        let grader = GraderConfig {
+            sources: vec![grader_path.to_string_lossy().to_string()],
+            headers: Vec::new(),
+        };
+
+        let compiler = CompilerRustc::new()?;
+        let src = "pub fn solve(x: i32) -> i32 { x * 2 }";
+        assert!(matches!(
+            compiler.compile_with_grader(src.into(), &grader),
+            CompileResult::OK(_)
+        ));
+        assert!(matches!(
+            compiler.compile_with_grader("pub fn other() {}".into(), &grader),
+            CompileResult::CompileError(_)
+        ));
+
+        Ok(())
+    }
+}
@@ -27,4 +27,6 @@ pub enum Error {
     Request(#[from] reqwest::Error),
     #[error("environment error")]
     Environment(String),
+    #[error("failed to read archive")]
+    Archive(#[from] zip::result::ZipError),
 }
\ No newline at end of file
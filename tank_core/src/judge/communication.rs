@@ -0,0 +1,448 @@
+use std::{
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io::Write,
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    compile::CompiledProgram,
+    error::Error,
+    error::Result,
+    probe::{ProcessBio, ProcessProbe},
+    problem::{CommunicationWiring, LimitConfig},
+    JudgeResult, JudgeStatus,
+};
+
+use super::{
+    exceeded_jury_limit,
+    interactive::{
+        kill_process, spawn_reader, spawn_user_program, spawn_waiter, spawn_writer, UserRun,
+    },
+    jury_verdict, read_in_background, spawn_jury_program, Judge,
+};
+
+pub struct CommunicationJudge {
+    /// programs run as processes, one for each, like an encoder followed by a decoder.
+    programs: Vec<CompiledProgram>,
+    input: Option<String>,
+    limit: LimitConfig,
+    manager: CompiledProgram,
+    manager_limit: LimitConfig,
+    wiring: CommunicationWiring,
+}
+
+enum CommunicationMessage {
+    /// Messages of user program are tagged with the index of process,
+    /// and those of manager with the index of its channel.
+    UserOut(usize, Vec<u8>),
+    ManagerOut(usize, Vec<u8>),
+    UserEof(usize),
+    ManagerEof(usize),
+    UserQuit(usize, ProcessBio),
+    ManagerQuit(ProcessBio),
+}
+
+/// Where the output of a process goes.
+enum Endpoint {
+    User(usize),
+    Manager(usize),
+}
+
+impl CommunicationWiring {
+    fn user_out(&self, index: usize, processes: usize) -> Endpoint {
+        match self {
+            CommunicationWiring::Star => Endpoint::Manager(index),
+            CommunicationWiring::Chain if index + 1 < processes => Endpoint::User(index + 1),
+            CommunicationWiring::Chain => Endpoint::Manager(0),
+        }
+    }
+
+    fn manager_out(&self, channel: usize) -> usize {
+        match self {
+            CommunicationWiring::Star => channel,
+            CommunicationWiring::Chain => 0,
+        }
+    }
+}
+
+fn make_fifo(path: &Path) -> Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Forward what manager writes to a fifo to broker.
+///
+/// The fifo is opened at once without blocking, so that manager is free to open it at any time.
+/// Reading it before any writer comes gives a false EOF, so the pipe only turns into
+/// a blocking one after manager has opened it.
+fn spawn_fifo_reader(
+    path: &Path,
+    channel: usize,
+    sender: Sender<CommunicationMessage>,
+) -> Result<()> {
+    let fifo = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    thread::spawn(move || {
+        let mut fd = libc::pollfd {
+            fd: fifo.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while unsafe { libc::poll(&mut fd, 1, -1) } < 0
+            && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+        {}
+        unsafe {
+            let flags = libc::fcntl(fifo.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(fifo.as_raw_fd(), libc::F_SETFL, flags & !libc::O_NONBLOCK);
+        }
+        spawn_reader(
+            fifo,
+            sender,
+            move |buf| CommunicationMessage::ManagerOut(channel, buf),
+            CommunicationMessage::ManagerEof(channel),
+        );
+    });
+    Ok(())
+}
+
+/// Write data sent by broker to a fifo read by manager. Opening blocks until manager opens it.
+fn spawn_fifo_writer(path: &Path) -> Sender<Vec<u8>> {
+    let path = path.to_path_buf();
+    let (sender, receiver) = channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let mut fifo = match OpenOptions::new().write(true).open(&path) {
+            Ok(fifo) => fifo,
+            Err(_) => return,
+        };
+        for buf in receiver {
+            if fifo.write_all(&buf).and_then(|_| fifo.flush()).is_err() {
+                // the other side has quitted, data left are useless.
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Wake up threads still waiting for manager on fifos, after manager has quitted.
+///
+/// Opening the other end of a fifo lets a blocked open return, and closing it at once
+/// makes the thread see EOF or a broken pipe.
+fn release_fifos(fifos: &[(PathBuf, PathBuf)]) {
+    for (from_user, to_user) in fifos {
+        let _: std::io::Result<File> = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(from_user);
+        let _: std::io::Result<File> = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(to_user);
+    }
+}
+
+impl CommunicationJudge {
+    /// Judge `programs` talking with `manager`, each of which runs under `limit` scaled for its language.
+    pub fn new(
+        programs: Vec<CompiledProgram>,
+        input: Option<String>,
+        limit: LimitConfig,
+        manager: CompiledProgram,
+        manager_limit: LimitConfig,
+        wiring: CommunicationWiring,
+    ) -> Self {
+        Self {
+            programs,
+            input,
+            limit,
+            manager,
+            manager_limit,
+            wiring,
+        }
+    }
+
+    /// Start one process of user program, which is told its index by the last argument.
+    fn spawn_user(&self, index: usize, sender: &Sender<CommunicationMessage>) -> Result<UserRun> {
+        let program = &self.programs[index];
+        let (probe, cin, cout, cerr) = spawn_user_program(
+            program,
+            vec![index.to_string()],
+            &program.scale_limit(&self.limit),
+        )?;
+
+        let pid = probe.get_pid();
+        spawn_reader(
+            cout,
+            sender.clone(),
+            move |buf| CommunicationMessage::UserOut(index, buf),
+            CommunicationMessage::UserEof(index),
+        );
+        spawn_waiter(probe, sender.clone(), move |res| {
+            CommunicationMessage::UserQuit(index, res)
+        });
+
+        Ok(UserRun {
+            pid,
+            cin: Some(spawn_writer(cin)),
+            eof: false,
+            res: None,
+            killed: false,
+            errout: read_in_background(cerr),
+        })
+    }
+}
+
+impl Judge for CommunicationJudge {
+    fn judge(self) -> Result<JudgeResult> {
+        let processes = self.programs.len();
+        if processes == 0 {
+            return Err(Error::Argument("no process to communicate".into()));
+        }
+
+        let (sender, receiver) = channel();
+
+        // fifos are named from the side of user, and passed to manager in pairs.
+        let fifo_dir = tempfile::TempDir::new()?;
+        let mut fifos = Vec::new();
+        let mut args: Vec<String> = self.input.clone().into_iter().collect();
+        if let CommunicationWiring::Star = self.wiring {
+            for index in 0..processes {
+                let from_user = fifo_dir.path().join(format!("user{}_out", index));
+                let to_user = fifo_dir.path().join(format!("user{}_in", index));
+                make_fifo(&from_user)?;
+                make_fifo(&to_user)?;
+                spawn_fifo_reader(&to_user, index, sender.clone())?;
+                args.push(from_user.to_string_lossy().to_string());
+                args.push(to_user.to_string_lossy().to_string());
+                fifos.push((from_user, to_user));
+            }
+        }
+
+        let manager_stdin = match self.wiring {
+            CommunicationWiring::Star => Stdio::null(),
+            CommunicationWiring::Chain => Stdio::piped(),
+        };
+        let manager = spawn_jury_program(&self.manager, args, &self.manager_limit, manager_stdin)?;
+
+        let manager_probe = ProcessProbe::new(manager.id())?;
+        let manager_pid = manager_probe.get_pid();
+        let mout = manager.stdout.ok_or(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "failed to open stdout for manager",
+        ))?;
+        let merr = manager.stderr.ok_or(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "failed to open stderr for manager",
+        ))?;
+        let merr = read_in_background(merr);
+
+        // inputs of manager, indexed by channel
+        let mut manager_in: Vec<Option<Sender<Vec<u8>>>> = match self.wiring {
+            CommunicationWiring::Star => {
+                // manager in star wiring talks by fifos only, its stdout is dropped.
+                read_in_background(mout);
+                fifos
+                    .iter()
+                    .map(|(from_user, _)| Some(spawn_fifo_writer(from_user)))
+                    .collect()
+            }
+            CommunicationWiring::Chain => {
+                let min = manager.stdin.ok_or(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "failed to open stdin for manager",
+                ))?;
+                spawn_reader(
+                    mout,
+                    sender.clone(),
+                    |buf| CommunicationMessage::ManagerOut(0, buf),
+                    CommunicationMessage::ManagerEof(0),
+                );
+                vec![Some(spawn_writer(min))]
+            }
+        };
+        let mut manager_eof = vec![false; manager_in.len()];
+        let mut manager_res: Option<ProcessBio> = None;
+        let mut manager_killed = false;
+        spawn_waiter(
+            manager_probe,
+            sender.clone(),
+            CommunicationMessage::ManagerQuit,
+        );
+
+        let mut users = Vec::new();
+        for index in 0..processes {
+            users.push(self.spawn_user(index, &sender)?);
+        }
+
+        let limits: Vec<LimitConfig> = self
+            .programs
+            .iter()
+            .map(|f| f.scale_limit(&self.limit))
+            .collect();
+        // the same as interactive mode, they are regarded as deadlocked when nothing happens
+        // between them for this long, and the whole communication must finish in twice of it.
+        let slowest = limits.iter().map(|f| f.time_limit).max().unwrap_or(0);
+        let idle_limit = Duration::from_millis(slowest + self.manager_limit.time_limit);
+        let wall_limit = idle_limit * 2;
+        // as interactor, manager only has its own time limit to finish once all processes quitted.
+        let grace_limit = Duration::from_millis(self.manager_limit.time_limit);
+
+        let mut users_quit_at: Option<Instant> = None;
+        let mut interaction_timeout = false;
+        let mut timed_out = false;
+
+        let start = Instant::now();
+        let mut last_active = start;
+        while manager_res.is_none()
+            || manager_eof.iter().any(|f| !f)
+            || users.iter().any(|f| f.res.is_none() || !f.eof)
+        {
+            let message = if timed_out {
+                // those killed are sure to quit
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                let mut deadline = (last_active + idle_limit).min(start + wall_limit);
+                if let Some(users_quit_at) = users_quit_at {
+                    deadline = deadline.min(users_quit_at + grace_limit);
+                }
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                receiver.recv_timeout(timeout)
+            };
+            match message {
+                Ok(CommunicationMessage::UserOut(index, buf)) => {
+                    last_active = Instant::now();
+                    let sink = match self.wiring.user_out(index, processes) {
+                        Endpoint::User(target) => &users[target].cin,
+                        Endpoint::Manager(channel) => &manager_in[channel],
+                    };
+                    if let Some(sink) = sink {
+                        let _ = sink.send(buf);
+                    }
+                }
+                Ok(CommunicationMessage::ManagerOut(channel, buf)) => {
+                    last_active = Instant::now();
+                    if let Some(cin) = &users[self.wiring.manager_out(channel)].cin {
+                        let _ = cin.send(buf);
+                    }
+                }
+                // pass EOF on by closing the input of the other side.
+                Ok(CommunicationMessage::UserEof(index)) => {
+                    users[index].eof = true;
+                    match self.wiring.user_out(index, processes) {
+                        Endpoint::User(target) => users[target].cin = None,
+                        Endpoint::Manager(channel) => manager_in[channel] = None,
+                    }
+                }
+                Ok(CommunicationMessage::ManagerEof(channel)) => {
+                    manager_eof[channel] = true;
+                    users[self.wiring.manager_out(channel)].cin = None;
+                }
+                Ok(CommunicationMessage::UserQuit(index, res)) => {
+                    last_active = Instant::now();
+                    users[index].res = Some(res);
+                    if users.iter().all(|f| f.res.is_some()) {
+                        users_quit_at = Some(last_active);
+                    }
+                }
+                Ok(CommunicationMessage::ManagerQuit(res)) => {
+                    last_active = Instant::now();
+                    manager_res = Some(res);
+                    // fifos manager never opened would keep the broker waiting forever.
+                    release_fifos(&fifos);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // kill whoever is still alive, and keep waiting for their quitting
+                    let mut user_alive = false;
+                    for user in users.iter_mut().filter(|f| f.res.is_none()) {
+                        kill_process(user.pid);
+                        user.killed = true;
+                        user_alive = true;
+                    }
+                    if manager_res.is_none() {
+                        kill_process(manager_pid);
+                        manager_killed = true;
+                    }
+                    // it is a timeout of communication only when both sides are alive.
+                    interaction_timeout = user_alive && manager_res.is_none();
+                    timed_out = true;
+                }
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            }
+        }
+        let wall_time = start.elapsed().as_millis() as u64;
+        let manager_res = manager_res.unwrap();
+
+        // every process is limited on its own
+        let mut time_usage = 0;
+        let mut peak_memory = 0;
+        let mut time_exceeded = false;
+        let mut memory_exceeded = false;
+        let mut user_crashed = false;
+        let mut user_errout = Vec::new();
+        for (user, limit) in users.into_iter().zip(limits.iter()) {
+            let res = user.res.unwrap();
+            time_usage = time_usage.max(res.get_time_usage());
+            peak_memory = peak_memory.max(res.get_peak_memory());
+            time_exceeded |= res.get_time_usage() >= limit.time_limit;
+            memory_exceeded |= res.get_peak_memory() >= limit.memory_limit * 1024;
+            user_crashed |= res.get_status() != 0 && !user.killed;
+            user_errout.extend(user.errout.join().unwrap()?);
+        }
+        let user_errout = String::from_utf8_lossy(&user_errout).to_string();
+        let manager_errout = String::from_utf8_lossy(&merr.join().unwrap()?).to_string();
+
+        let manager_failed =
+            manager_killed || exceeded_jury_limit(&manager_res, &self.manager_limit);
+
+        // check result
+        let mut judge_status = if time_exceeded {
+            JudgeStatus::TimeLimitExceeded
+        } else if memory_exceeded {
+            JudgeStatus::MemoryLimitExceeded
+        } else if user_errout.find("bad_alloc").is_some() {
+            JudgeStatus::MemoryLimitExceeded
+        } else if interaction_timeout {
+            JudgeStatus::InteractionTimeLimitExceeded
+        } else if user_crashed {
+            // manager may fail on what a crashed process leaves, which is not its fault.
+            JudgeStatus::RuntimeError
+        } else if manager_failed {
+            JudgeStatus::JudgementFailed
+        } else {
+            JudgeStatus::Uncertain
+        };
+
+        let mut message = None;
+        if let JudgeStatus::Uncertain = judge_status {
+            let (status, reason) = jury_verdict("manager", &manager_errout);
+            judge_status = status;
+            message = reason;
+        }
+
+        let judge_result = JudgeResult {
+            status: judge_status,
+            time: time_usage.into(),
+            memory: peak_memory.into(),
+            interactor_time: manager_res.get_time_usage().into(),
+            wall_time: wall_time.into(),
+            stdin: None,
+            stdout: None,
+            stderr: user_errout.into(),
+            transcript: None,
+            message,
+            score: None,
+        };
+        Ok(judge_result)
+    }
+}
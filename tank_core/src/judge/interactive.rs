@@ -1,275 +1,601 @@
 use std::{
     fs,
     path::Path,
-    process::{Command, Stdio},
-    sync::{mpsc::channel, Arc, Mutex},
-    thread,
+    os::unix::io::AsRawFd,
+    process::{ChildStderr, ChildStdin, ChildStdout, Command, Stdio},
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crate::{JudgeResult, JudgeStatus, compile::CompiledProgram, problem::LimitConfig, error::Error, error::Result, probe::ProcessProbe};
+use crate::{
+    compile::CompiledProgram,
+    error::Result,
+    probe::{ProcessBio, ProcessProbe},
+    problem::LimitConfig,
+    transcript::{Direction, Transcript},
+    JudgeResult, JudgeStatus,
+};
 
-use super::{get_path_of_tankcell, Judge};
+use super::{
+    exceeded_jury_limit, get_path_of_tankcell, jury_verdict, read_in_background,
+    spawn_jury_program, Judge,
+};
 
-use std::io::{Write,Read};
+use std::io::{Read, Write};
 
 pub struct InteractiveJudge {
     program: CompiledProgram,
     input: Option<String>,
     limit: LimitConfig,
-    interactor: String,
+    interactor: CompiledProgram,
+    interactor_limit: LimitConfig,
+    transcript_file: Option<String>,
+    max_runs: Option<u64>,
 }
 
+/// Line written to stderr by interactor to request one more run of user program.
+pub const NEXT_RUN_REQUEST: &str = "next_run";
+
+/// Line written to stdin of interactor once a run closes its output, in multi-run mode.
+pub const RUN_EOF_NOTICE: &str = "eof";
+
 enum InteractiveMessage {
+    /// Eof and quit of user program are tagged with the index of run.
     UserOut(Vec<u8>),
     InteractorOut(Vec<u8>),
-    UserQuit,
-    InteractorQuit,
+    UserEof(usize),
+    InteractorEof,
+    UserQuit(usize, ProcessBio),
+    InteractorQuit(ProcessBio),
+    NextRun,
+}
+
+/// Forward everything read from `reader` to broker, ending with `eof`.
+pub(super) fn spawn_reader<R, M, F>(mut reader: R, sender: Sender<M>, wrap: F, eof: M)
+where
+    R: Read + Send + 'static,
+    M: Send + 'static,
+    F: Fn(Vec<u8>) -> M + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(len) if len > 0 => {
+                    if sender.send(wrap(buf[..len].to_vec())).is_err() {
+                        break;
+                    }
+                }
+                // both reading nothing and an error mean the pipe is closed by the other side.
+                _ => {
+                    let _ = sender.send(eof);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Write data sent by broker to `writer` in another thread, so that a program refusing
+/// to read never blocks broker. The pipe is closed once the returned sender is dropped.
+pub(super) fn spawn_writer<W>(mut writer: W) -> Sender<Vec<u8>>
+where
+    W: Write + Send + 'static,
+{
+    let (sender, receiver) = channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for buf in receiver {
+            if writer.write_all(&buf).and_then(|_| writer.flush()).is_err() {
+                // the other side has quitted, data left are useless.
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Wait for a process in another thread, and report to broker when it quits.
+pub(super) fn spawn_waiter<M, F>(probe: ProcessProbe, sender: Sender<M>, wrap: F)
+where
+    M: Send + 'static,
+    F: Fn(ProcessBio) -> M + Send + 'static,
+{
+    thread::spawn(move || {
+        let _ = sender.send(wrap(probe.watching()));
+    });
+}
+
+/// Read stdout and stderr of interactor in one thread. Requests for new runs in stderr are
+/// passed on to broker if `accept_requests`, and the other lines are kept as the response.
+///
+/// A request must reach broker after the outputs written before it, so the two pipes are
+/// polled together, and stdout is drained before a request is passed on.
+fn spawn_interactor_reader(
+    mut iout: ChildStdout,
+    mut ierr: ChildStderr,
+    sender: Sender<InteractiveMessage>,
+    accept_requests: bool,
+) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut response = Vec::new();
+        let mut line = Vec::new();
+        let mut out_buf: [u8; 1024] = [0; 1024];
+        // large enough to take all in the pipe at once
+        let mut err_buf = vec![0u8; 65536];
+        let mut out_open = true;
+        let mut err_open = true;
+
+        // pass on one read of stdout, returning whether it is still open.
+        let mut forward_out = |iout: &mut ChildStdout| match iout.read(&mut out_buf) {
+            Ok(len) if len > 0 => {
+                let _ = sender.send(InteractiveMessage::InteractorOut(out_buf[..len].to_vec()));
+                true
+            }
+            // both reading nothing and an error mean the pipe is closed by the other side.
+            _ => {
+                let _ = sender.send(InteractiveMessage::InteractorEof);
+                false
+            }
+        };
+
+        while out_open || err_open {
+            // negative fd is ignored by poll
+            let mut fds = [
+                libc::pollfd {
+                    fd: if err_open { ierr.as_raw_fd() } else { -1 },
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: if out_open { iout.as_raw_fd() } else { -1 },
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+                let err = std::io::Error::last_os_error();
+                if let std::io::ErrorKind::Interrupted = err.kind() {
+                    continue;
+                }
+                return Err(err);
+            }
+
+            if fds[0].revents != 0 {
+                let len = ierr.read(&mut err_buf).unwrap_or(0);
+                if len == 0 {
+                    err_open = false;
+                }
+                for &byte in &err_buf[..len] {
+                    if byte != b'\n' {
+                        line.push(byte);
+                        continue;
+                    }
+                    if accept_requests
+                        && String::from_utf8_lossy(&line).trim() == NEXT_RUN_REQUEST
+                    {
+                        // what is written before the request is already in the pipe,
+                        // and goes to the current run.
+                        while out_open && poll_ready(&iout) {
+                            out_open = forward_out(&mut iout);
+                        }
+                        // all that polled is taken.
+                        fds[1].revents = 0;
+                        let _ = sender.send(InteractiveMessage::NextRun);
+                    } else {
+                        response.extend(line.iter());
+                        response.push(b'\n');
+                    }
+                    line.clear();
+                }
+            }
+            if out_open && fds[1].revents != 0 {
+                out_open = forward_out(&mut iout);
+            }
+        }
+        response.extend(line);
+
+        Ok(response)
+    })
+}
+
+/// Whether reading `reader` would not block right now.
+fn poll_ready<R: AsRawFd>(reader: &R) -> bool {
+    let mut fd = libc::pollfd {
+        fd: reader.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, 0) > 0 }
+}
+
+/// A running instance of user program.
+pub(super) struct UserRun {
+    pub(super) pid: u32,
+    pub(super) cin: Option<Sender<Vec<u8>>>,
+    pub(super) eof: bool,
+    pub(super) res: Option<ProcessBio>,
+    pub(super) killed: bool,
+    pub(super) errout: JoinHandle<std::io::Result<Vec<u8>>>,
 }
 
 impl InteractiveJudge {
-    pub fn new(program: CompiledProgram, input: Option<String>, limit: LimitConfig, interactor: String) -> Self {
+    pub fn new(
+        program: CompiledProgram,
+        input: Option<String>,
+        limit: LimitConfig,
+        interactor: CompiledProgram,
+        interactor_limit: LimitConfig,
+        transcript_file: Option<String>,
+        max_runs: Option<u64>,
+    ) -> Self {
         Self {
             program,
             input,
             limit,
             interactor,
+            interactor_limit,
+            transcript_file,
+            max_runs,
         }
     }
+
+    /// Start a new run of user program, connecting its outputs to broker.
+    fn spawn_user(&self, index: usize, sender: &Sender<InteractiveMessage>) -> Result<UserRun> {
+        let (probe, cin, cout, cerr) = spawn_user_program(&self.program, Vec::new(), &self.limit)?;
+
+        let pid = probe.get_pid();
+        spawn_reader(
+            cout,
+            sender.clone(),
+            InteractiveMessage::UserOut,
+            InteractiveMessage::UserEof(index),
+        );
+        spawn_waiter(probe, sender.clone(), move |res| {
+            InteractiveMessage::UserQuit(index, res)
+        });
+
+        Ok(UserRun {
+            pid,
+            cin: Some(spawn_writer(cin)),
+            eof: false,
+            res: None,
+            killed: false,
+            errout: read_in_background(cerr),
+        })
+    }
+}
+
+/// Spawn user program in a cell with all of its standard streams piped.
+pub(super) fn spawn_user_program(
+    program: &CompiledProgram,
+    args: Vec<String>,
+    limit: &LimitConfig,
+) -> Result<(ProcessProbe, ChildStdin, ChildStdout, ChildStderr)> {
+    let path = Path::new(&program.path);
+    let path = fs::canonicalize(path)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let command = Command::new(get_path_of_tankcell())
+        .arg(path)
+        .arg(format!("-m {}", limit.memory_limit))
+        .arg(format!("-t {}", limit.time_limit))
+        .args(program.cell_args())
+        .arg("--")
+        .args(program.run_args(limit))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let probe = ProcessProbe::new(command.id())?;
+    let cin = command.stdin.ok_or(std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "failed to open stdin",
+    ))?;
+    let cout = command.stdout.ok_or(std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "failed to open stdout",
+    ))?;
+    let cerr = command.stderr.ok_or(std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "failed to open stderr",
+    ))?;
+
+    Ok((probe, cin, cout, cerr))
+}
+
+pub(super) fn kill_process(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
 }
 
 impl Judge for InteractiveJudge {
     fn judge(self) -> Result<JudgeResult> {
-        let interactor_fullpath = fs::canonicalize(self.interactor).unwrap();
-        let interactor_fullpath = Path::new(&interactor_fullpath);
-
-        let interactor=if let Some(input)=self.input{
-            Command::new(interactor_fullpath)
-            .arg(input)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap()
-        }else{
-            Command::new(interactor_fullpath)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap()
-        };
+        let args = self.input.clone().into_iter().collect();
+        let interactor = spawn_jury_program(
+            &self.interactor,
+            args,
+            &self.interactor_limit,
+            Stdio::piped(),
+        )?;
 
-        let _interactor_pid = interactor.id();
-        let mut iin = interactor.stdin.ok_or(std::io::Error::new(
+        let interactor_probe = ProcessProbe::new(interactor.id())?;
+        let iin = interactor.stdin.ok_or(std::io::Error::new(
             std::io::ErrorKind::BrokenPipe,
             "failed to open stdin for interactor",
         ))?;
-        let mut iout = interactor.stdout.ok_or(std::io::Error::new(
+        let iout = interactor.stdout.ok_or(std::io::Error::new(
             std::io::ErrorKind::BrokenPipe,
             "failed to open stdout for interactor",
         ))?;
-        let mut ierr = interactor.stderr.ok_or(std::io::Error::new(
+        let ierr = interactor.stderr.ok_or(std::io::Error::new(
             std::io::ErrorKind::BrokenPipe,
             "failed to open stderr for interactor",
         ))?;
 
-        let path = Path::new(&self.program.path);
-        let path = fs::canonicalize(path)
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        let command = Command::new(get_path_of_tankcell())
-            .arg(path)
-            .arg(format!("-m {}", self.limit.memory_limit))
-            .arg(format!("-t {}", self.limit.time_limit))
-            .arg("-p minimum")
-            .arg("--")
-            .args(self.program.args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
-
-        let pid = command.id();
-        let probe = ProcessProbe::new(pid)?;
-        let mut cin = command.stdin.ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stdin",
-        ))?;
-        let mut cout = command.stdout.ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stdout",
-        ))?;
-        let mut cerr = command.stderr.ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stderr",
-        ))?;
+        let multi_run = self.max_runs.is_some();
+        let max_runs = self.max_runs.unwrap_or(1) as usize;
+        let interactor_pid = interactor_probe.get_pid();
 
         // channel switching inputs and outputs of interactor and user program
         let (sender, receiver) = channel();
+        let ierr = spawn_interactor_reader(iout, ierr, sender.clone(), multi_run);
+        spawn_waiter(
+            interactor_probe,
+            sender.clone(),
+            InteractiveMessage::InteractorQuit,
+        );
+        let mut iin = Some(spawn_writer(iin));
+        // whether the last output of user program ends in the middle of a line
+        let mut line_open = false;
+        let mut interactor_eof = false;
+        let mut interactor_res: Option<ProcessBio> = None;
+        let mut interactor_killed = false;
 
-        let csender = sender.clone();
-        let isender = sender.clone();
-        let _user_thread = thread::spawn(move || {
-            let mut buf: [u8; 1024] = [0; 1024];
-            loop {
-                let res = cout.read(&mut buf);
-                match res {
-                    Ok(len) => {
-                        // TODO: len=0
-                        if len == 0 {
-                            continue;
-                        }
-                        let mut vec = Vec::from(buf);
-                        vec.truncate(len);
-                        csender.send(InteractiveMessage::UserOut(vec)).unwrap();
+        let mut runs = vec![self.spawn_user(0, &sender)?];
+        // outputs of interactor waiting for the next run to start
+        let mut pending_input: Vec<Vec<u8>> = Vec::new();
+        let mut pending_run = false;
+        let mut too_many_runs = false;
+
+        // the two are regarded as deadlocked when nothing happens between them for this long,
+        // and the whole interaction must finish in twice of it.
+        let idle_limit =
+            Duration::from_millis(self.limit.time_limit + self.interactor_limit.time_limit);
+        let wall_limit = idle_limit * 2;
+        // once user program has quitted, interactor only has its own time limit to finish.
+        let grace_limit = Duration::from_millis(self.interactor_limit.time_limit);
+
+        let mut transcript = Transcript::new();
+        // when the current run quitted, while interactor may be still working.
+        let mut user_quit_at: Option<Instant> = None;
+        let mut interaction_timeout = false;
+        let mut timed_out = false;
+        // whether interactor closed its output, as it quits, before the current run did.
+        let mut interactor_quit_first = false;
+
+        let start = Instant::now();
+        let mut last_active = start;
+        // wait until all quit and all of their outputs are passed on
+        while interactor_res.is_none()
+            || !interactor_eof
+            || runs.iter().any(|f| f.res.is_none() || !f.eof)
+        {
+            let message = if timed_out {
+                // those killed are sure to quit
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                let mut deadline = (last_active + idle_limit).min(start + wall_limit);
+                if let Some(user_quit_at) = user_quit_at {
+                    deadline = deadline.min(user_quit_at + grace_limit);
+                }
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                receiver.recv_timeout(timeout)
+            };
+            let current = runs.len() - 1;
+            match message {
+                Ok(InteractiveMessage::UserOut(buf)) => {
+                    last_active = Instant::now();
+                    transcript.push(
+                        (last_active - start).as_millis() as u64,
+                        Direction::UserToInteractor,
+                        buf.clone(),
+                    );
+                    line_open = buf.last() != Some(&b'\n');
+                    if let Some(iin) = &iin {
+                        let _ = iin.send(buf);
                     }
-                    Err(err) => {
-                        if let std::io::ErrorKind::BrokenPipe = err.kind(){
-                            // it seems like this error can be regarded that the program quits.
-                            csender.send(InteractiveMessage::UserQuit).unwrap();
-                        }else{
-                            panic!("error when trying reading from user program: {}",err);
-                        }
+                }
+                Ok(InteractiveMessage::InteractorOut(buf)) => {
+                    last_active = Instant::now();
+                    transcript.push(
+                        (last_active - start).as_millis() as u64,
+                        Direction::InteractorToUser,
+                        buf.clone(),
+                    );
+                    if pending_run {
+                        pending_input.push(buf);
+                    } else if let Some(cin) = &runs[current].cin {
+                        let _ = cin.send(buf);
                     }
                 }
-            }
-        });
-        let _interactor_thread = thread::spawn(move || {
-            let mut buf: [u8; 1024] = [0; 1024];
-            loop {
-                match iout.read(&mut buf) {
-                    Ok(len) => {
-                        // TODO: len=0
-                        if len == 0 {
-                            continue;
-                        }
-                        let mut vec = Vec::from(buf);
-                        vec.truncate(len);
-                        isender
-                            .send(InteractiveMessage::InteractorOut(vec))
-                            .unwrap();
+                // pass EOF on by closing the stdin of the other side.
+                // in multi-run mode, interactor may keep talking to the next run, so it is
+                // told in a line of its own, and its stdin is closed after the last run.
+                Ok(InteractiveMessage::UserEof(index)) => {
+                    runs[index].eof = true;
+                    if let (true, Some(iin)) = (multi_run, &iin) {
+                        let mut notice = if line_open { "\n" } else { "" }.to_string();
+                        notice.push_str(RUN_EOF_NOTICE);
+                        notice.push('\n');
+                        let _ = iin.send(notice.into_bytes());
+                        line_open = false;
                     }
-                    Err(err) => {
-                        if let std::io::ErrorKind::BrokenPipe = err.kind(){
-                            // it seems like this error can be regarded that the program quits.
-                            isender.send(InteractiveMessage::InteractorQuit).unwrap();
-                        }else{
-                            panic!("error when trying reading from interactor: {}",err);
-                        }
-                        println!("interactor reading error: {:?}", err);
+                    if runs.len() >= max_runs {
+                        iin = None;
                     }
                 }
-            }
-        });
-
-        let input = Arc::new(Mutex::new(Vec::new()));
-        let output = Arc::new(Mutex::new(Vec::new()));
-
-        let b_input = input.clone();
-        let b_output = output.clone();
-        let broker = thread::spawn(move || {
-            loop {
-                match receiver.recv().unwrap() {
-                    InteractiveMessage::UserOut(buf) => {
-                        b_output
-                            .lock()
-                            .unwrap()
-                            .push(std::string::String::from_utf8(buf.clone()).unwrap());
-                        if let Err(err) = iin.write_all(buf.as_slice()) {
-                            if let std::io::ErrorKind::BrokenPipe = err.kind(){
-                                break;
-                            }else{
-                                panic!("error when trying writing to interactor: {}",err);
-                            }
-                        }
+                Ok(InteractiveMessage::InteractorEof) => {
+                    // either side gets EOF only after that of the other is passed on, so the one
+                    // closing its output first is the one quitting first.
+                    interactor_quit_first = !runs[current].eof;
+                    interactor_eof = true;
+                    runs[current].cin = None;
+                }
+                Ok(InteractiveMessage::UserQuit(index, res)) => {
+                    last_active = Instant::now();
+                    runs[index].res = Some(res);
+                    if index == current {
+                        user_quit_at = Some(last_active);
                     }
-                    InteractiveMessage::InteractorOut(buf) => {
-                        b_input
-                            .lock()
-                            .unwrap()
-                            .push(std::string::String::from_utf8(buf.clone()).unwrap());
-                        if let Err(err) = cin.write_all(buf.as_slice()) {
-                            if let std::io::ErrorKind::BrokenPipe = err.kind(){
-                                break;
-                            }else{
-                                panic!("error when trying writing to user program: {}",err);
-                            }
-                        }
+                }
+                Ok(InteractiveMessage::InteractorQuit(res)) => {
+                    last_active = Instant::now();
+                    interactor_res = Some(res);
+                }
+                Ok(InteractiveMessage::NextRun) => {
+                    last_active = Instant::now();
+                    if runs.len() >= max_runs {
+                        // interactor is asking for more than problem allows, no more input for it
+                        too_many_runs = true;
+                        iin = None;
+                    } else {
+                        // let the current run finish first, it will be replaced once it quits
+                        pending_run = true;
+                        runs[current].cin = None;
                     }
-                    InteractiveMessage::UserQuit => {
-                        break;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // kill whoever is still alive, and keep waiting for their quitting
+                    let user_alive = runs[current].res.is_none();
+                    if user_alive {
+                        kill_process(runs[current].pid);
+                        runs[current].killed = true;
                     }
-                    InteractiveMessage::InteractorQuit => {
-                        break;
+                    if interactor_res.is_none() {
+                        kill_process(interactor_pid);
+                        interactor_killed = true;
                     }
+                    // it is a timeout of interaction only when both are alive.
+                    interaction_timeout = user_alive && interactor_res.is_none();
+                    timed_out = true;
                 }
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
             }
-        });
 
-        // wait for user quitting
-        // need not to kill user program when interactor quits first. it is seen as TLE
-        let probe_res = probe.watching();
-        // notice broker just in case
-        sender.send(InteractiveMessage::UserQuit).unwrap();
-        // ~~interactor MUST quit before user, or it will be killed~~
-        // this may cause interactor being killed before we get result from it.
-        // unsafe {
-        //     // maybe there is a more elegant way to do it
-        //     kill(interactor_pid as i32, SIGKILL);
-        // }
-        // wait for broker to quit
-        broker.join().unwrap();
-
-        // join output and input
-        let output = output.lock().unwrap().join("\n");
-        let input = input.lock().unwrap().join("\n");
-
-        let mut user_errout = String::new();
-        cerr.read_to_string(&mut user_errout)?;
-        let mut interactor_errout = String::new();
-        ierr.read_to_string(&mut interactor_errout)?;
+            // the next run starts only after the current one is over, and interactor knows it.
+            if pending_run
+                && runs[current].res.is_some()
+                && runs[current].eof
+                && interactor_res.is_none()
+                && !timed_out
+            {
+                pending_run = false;
+                let mut run = self.spawn_user(runs.len(), &sender)?;
+                for buf in pending_input.drain(..) {
+                    let _ = run.cin.as_ref().unwrap().send(buf);
+                }
+                if interactor_eof {
+                    run.cin = None;
+                }
+                runs.push(run);
+                user_quit_at = None;
+            }
+        }
+        let wall_time = start.elapsed().as_millis() as u64;
+        let interactor_res = interactor_res.unwrap();
+
+        if let Some(transcript_file) = &self.transcript_file {
+            transcript.save(transcript_file)?;
+        }
+        let output = String::from_utf8_lossy(&transcript.collect(Direction::UserToInteractor))
+            .to_string();
+        let input = String::from_utf8_lossy(&transcript.collect(Direction::InteractorToUser))
+            .to_string();
+
+        // usage of all runs are put together
+        let mut time_usage = 0;
+        let mut peak_memory = 0;
+        let mut user_crashed = false;
+        let mut user_errout = Vec::new();
+        for run in runs {
+            let res = run.res.unwrap();
+            time_usage += res.get_time_usage();
+            peak_memory = peak_memory.max(res.get_peak_memory());
+            user_crashed |= res.get_status() != 0 && !run.killed;
+            user_errout.extend(run.errout.join().unwrap()?);
+        }
+        let user_errout = String::from_utf8_lossy(&user_errout).to_string();
+        let interactor_errout = String::from_utf8_lossy(&ierr.join().unwrap()?).to_string();
+
+        let interactor_failed =
+            interactor_killed || exceeded_jury_limit(&interactor_res, &self.interactor_limit);
+        let (verdict, reason) = jury_verdict("interactor", &interactor_errout);
+        let rejected = matches!(
+            verdict,
+            JudgeStatus::WrongAnswer | JudgeStatus::PresentationError
+        );
 
         // check result
-        let mut judge_status = if probe_res.get_time_usage() >= self.limit.time_limit {
+        let mut judge_status = if time_usage >= self.limit.time_limit {
             JudgeStatus::TimeLimitExceeded
-        } else if probe_res.get_peak_memory() >= self.limit.memory_limit * 1024 {
+        } else if peak_memory >= self.limit.memory_limit * 1024 {
             JudgeStatus::MemoryLimitExceeded
         } else if user_errout.find("bad_alloc").is_some() {
             // fix: struct like vector which does not allocate memory gradually
             // may touch the wall when memory is still below the limit
             // even we give two times more of it.
             JudgeStatus::MemoryLimitExceeded
-        } else if probe_res.get_status() != 0 {
+        } else if interaction_timeout {
+            JudgeStatus::InteractionTimeLimitExceeded
+        } else if interactor_quit_first && !interactor_failed && rejected {
+            // interactor has rejected the program before it quits, what the program does after
+            // EOF, like crashing on it, is no matter.
+            JudgeStatus::Uncertain
+        } else if user_crashed {
+            // interactor may fail on what a crashed run leaves, which is not its fault.
             JudgeStatus::RuntimeError
+        } else if too_many_runs {
+            // interactor does not agree with the problem on the number of runs.
+            JudgeStatus::JudgementFailed
+        } else if interactor_failed {
+            // interactor gets EOF once user program quits, so it has no excuse to crash or hang.
+            JudgeStatus::JudgementFailed
         } else {
+            // interactor exited first, or after the user, in time. it has the final say.
             JudgeStatus::Uncertain
         };
 
-        let interactor_errout: Vec<&str> = interactor_errout.lines().map(|f| f.trim()).collect();
-
+        let mut message = None;
         if let JudgeStatus::Uncertain = judge_status {
-            if interactor_errout.len() <= 0 {
-                return Err(Error::UserProgram("interactor gives no response".into()));
-            }
-            judge_status = match interactor_errout[0] {
-                "same" => JudgeStatus::Accept,
-                "different" => JudgeStatus::WrongAnswer,
-                "pattern_different" => JudgeStatus::PresentationError,
-                _ => Err(Error::UserProgram("interactor gives unknown result".into()))?,
-            }
+            judge_status = verdict;
+            // tell which side left the interaction unfinished
+            message = match (rejected, interactor_quit_first) {
+                (true, true) => Some("interactor exited before program".into()),
+                (true, false) => Some("program exited before interactor".into()),
+                _ => reason,
+            };
         }
 
         let judge_result = JudgeResult {
             status: judge_status,
-            time: probe_res.get_time_usage().into(),
-            memory: probe_res.get_peak_memory().into(),
+            time: time_usage.into(),
+            memory: peak_memory.into(),
+            interactor_time: interactor_res.get_time_usage().into(),
+            wall_time: wall_time.into(),
             stdin: input.into(),
             stdout: output.into(),
             stderr: user_errout.into(),
+            transcript: transcript.into(),
+            message,
+            score: None,
         };
         Ok(judge_result)
     }
@@ -1,16 +1,35 @@
+mod communication;
 mod interactive;
 mod normal;
+mod output_only;
+mod script;
 mod special;
 
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::mpsc::channel,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+pub use self::output_only::{collect_outputs, OutputCheck};
+pub use self::script::compile_checker_script;
+use self::{
+    communication::CommunicationJudge, interactive::InteractiveJudge, normal::NormalJudge,
+    output_only::OutputOnlyJudge, script::ScriptJudge, special::SpecialJudge,
+};
+use rhai::AST;
 
-use self::{interactive::InteractiveJudge, normal::NormalJudge, special::SpecialJudge};
 use crate::{
     compare::ComparisionMode,
     compile::CompiledProgram,
     error::{Error, Result},
-    problem::{ComparisionModeConfig, LimitConfig},
-    JudgeResult,
+    probe::{ProcessBio, ProcessProbe},
+    problem::{CommunicationWiring, ComparisionModeConfig, LimitConfig},
+    JudgeResult, JudgeStatus,
 };
 
 pub trait Judge {
@@ -24,6 +43,7 @@ pub fn launch_normal_case_judge(
     limit: LimitConfig,
     comparision_mode: &ComparisionModeConfig,
 ) -> Result<JudgeResult> {
+    let limit = program.scale_limit(&limit);
     let path = Path::new(&program.path);
     let input_file_path = Path::new(input_file);
     let answer_file_path = Path::new(answer_file);
@@ -33,14 +53,13 @@ pub fn launch_normal_case_judge(
     }
 
     let input = fs::read_to_string(input_file_path)?;
-    let answer = fs::read_to_string(answer_file_path)?;
 
     let comparation: Box<dyn ComparisionMode> = comparision_mode.into();
 
     let judge = NormalJudge::new(
         program,
         input,
-        answer,
+        answer_file.to_string(),
         limit.memory_limit,
         limit.time_limit,
         comparation,
@@ -50,46 +69,144 @@ pub fn launch_normal_case_judge(
     Ok(judge_result)
 }
 
+/// Judge a case by checker, which should be built by `prepare_jury_program`.
 pub fn launch_special_case_judge(
     program: CompiledProgram,
     input_file: &str,
-    checker: &str,
+    answer_file: Option<&str>,
+    checker: &CompiledProgram,
     limit: LimitConfig,
+    checker_limit: LimitConfig,
 ) -> Result<JudgeResult> {
+    let limit = program.scale_limit(&limit);
     let path = Path::new(&program.path);
     let input_file_path = Path::new(input_file);
-    let checker_path = Path::new(checker);
 
-    if !path.exists() || !input_file_path.exists() || !checker_path.exists() {
-        return Err(Error::NotFound(format!("code, input or checker file")));
+    if !path.exists() || !input_file_path.exists() {
+        return Err(Error::NotFound(format!("code or input file")));
     }
 
     let input = fs::read_to_string(input_file_path)?;
+    let answer = if let Some(answer_file) = answer_file {
+        let answer_file_path = Path::new(answer_file);
+        if !answer_file_path.exists() {
+            return Err(Error::NotFound(answer_file.to_string()));
+        }
+
+        Some(fs::read_to_string(answer_file_path)?)
+    } else {
+        None
+    };
 
     let judge = SpecialJudge::new(
         program,
         input,
+        answer,
+        limit.memory_limit,
+        limit.time_limit,
+        checker.clone(),
+        checker_limit,
+    );
+    let judge_result = judge.judge()?;
+
+    Ok(judge_result)
+}
+
+/// Judge a case by checker script, which should be compiled by `compile_checker_script`.
+pub fn launch_script_case_judge(
+    program: CompiledProgram,
+    input_file: &str,
+    answer_file: Option<&str>,
+    checker: &AST,
+    limit: LimitConfig,
+) -> Result<JudgeResult> {
+    let limit = program.scale_limit(&limit);
+    let path = Path::new(&program.path);
+    let input_file_path = Path::new(input_file);
+
+    if !path.exists() || !input_file_path.exists() {
+        return Err(Error::NotFound(format!("code or input file")));
+    }
+
+    let input = fs::read_to_string(input_file_path)?;
+    let answer = if let Some(answer_file) = answer_file {
+        let answer_file_path = Path::new(answer_file);
+        if !answer_file_path.exists() {
+            return Err(Error::NotFound(answer_file.to_string()));
+        }
+
+        Some(fs::read_to_string(answer_file_path)?)
+    } else {
+        None
+    };
+
+    let judge = ScriptJudge::new(
+        program,
+        input,
+        answer,
         limit.memory_limit,
         limit.time_limit,
-        checker.into(),
+        checker,
     );
     let judge_result = judge.judge()?;
 
     Ok(judge_result)
 }
 
+/// Judge a case by interactor, which should be built by `prepare_jury_program`.
 pub fn launch_interactive_case_judge(
     program: CompiledProgram,
     input_file: Option<String>,
-    interactor: &str,
+    interactor: &CompiledProgram,
     limit: LimitConfig,
+    interactor_limit: LimitConfig,
+    transcript_file: Option<&str>,
+    max_runs: Option<u64>,
 ) -> Result<JudgeResult> {
+    let limit = program.scale_limit(&limit);
     let path = Path::new(&program.path);
 
-    let interactor_path = Path::new(interactor);
+    if !path.exists() {
+        return Err(Error::NotFound(format!("code file")));
+    }
+
+    let input = if let Some(input_file) = input_file {
+        let input_file_path = Path::new(&input_file);
+        if !input_file_path.exists() {
+            return Err(Error::NotFound(input_file.to_string()));
+        }
+
+        Some(fs::read_to_string(input_file_path)?)
+    } else {
+        None
+    };
+    let judge = InteractiveJudge::new(
+        program,
+        input,
+        limit,
+        interactor.clone(),
+        interactor_limit,
+        transcript_file.map(|f| f.to_string()),
+        max_runs,
+    );
+    let judge_result = judge.judge()?;
+
+    Ok(judge_result)
+}
 
-    if !path.exists() || !interactor_path.exists() {
-        return Err(Error::NotFound(format!("code, input or interactor file")));
+/// Judge `programs` run as processes of communication, one for each. Every one of them is
+/// limited by `limit` scaled for its own language, and manager should be built by
+/// `prepare_jury_program`.
+pub fn launch_communication_case_judge(
+    programs: Vec<CompiledProgram>,
+    input_file: Option<String>,
+    manager: &CompiledProgram,
+    wiring: CommunicationWiring,
+    limit: LimitConfig,
+    manager_limit: LimitConfig,
+) -> Result<JudgeResult> {
+    if programs.iter().any(|f| !Path::new(&f.path).exists()) {
+        return Err(Error::NotFound(format!("code file")));
     }
 
     let input = if let Some(input_file) = input_file {
@@ -102,7 +219,47 @@ pub fn launch_interactive_case_judge(
     } else {
         None
     };
-    let judge = InteractiveJudge::new(program, input, limit, interactor.into());
+    let judge = CommunicationJudge::new(
+        programs,
+        input,
+        limit,
+        manager.clone(),
+        manager_limit,
+        wiring,
+    );
+    let judge_result = judge.judge()?;
+
+    Ok(judge_result)
+}
+
+/// Judge an output submitted directly, `None` if it is missing.
+pub fn launch_output_only_case_judge(
+    output: Option<String>,
+    input_file: &str,
+    answer_file: Option<&str>,
+    check: &OutputCheck,
+) -> Result<JudgeResult> {
+    let input_file_path = Path::new(input_file);
+    if !input_file_path.exists() {
+        return Err(Error::NotFound(input_file.to_string()));
+    }
+
+    let input = fs::read_to_string(input_file_path)?;
+    let answer = if let Some(answer_file) = answer_file {
+        let answer_file_path = Path::new(answer_file);
+        if !answer_file_path.exists() {
+            return Err(Error::NotFound(answer_file.to_string()));
+        }
+
+        Some(fs::read_to_string(answer_file_path)?)
+    } else {
+        None
+    };
+    if let (OutputCheck::Comparision(_), None) = (check, &answer) {
+        return Err(Error::NotFound(format!("answer file of `{}`", input_file)));
+    }
+
+    let judge = OutputOnlyJudge::new(output, input, answer, check);
     let judge_result = judge.judge()?;
 
     Ok(judge_result)
@@ -118,9 +275,214 @@ pub fn get_path_of_tankcell() -> String {
         .to_string()
 }
 
+/// Spawn a jury program, checker or interactor, in a cell limited by its own `LimitConfig`.
+pub(crate) fn spawn_jury_program(
+    program: &CompiledProgram,
+    args: Vec<String>,
+    limit: &LimitConfig,
+    stdin: Stdio,
+) -> Result<Child> {
+    let path = fs::canonicalize(&program.path)?
+        .to_string_lossy()
+        .to_string();
+
+    let child = Command::new(get_path_of_tankcell())
+        .arg(path)
+        .arg(format!("-m {}", limit.memory_limit))
+        .arg(format!("-t {}", limit.time_limit))
+        .args(program.cell_args())
+        .arg("--")
+        .args(program.run_args(limit))
+        .args(args)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    Ok(child)
+}
+
+/// Run the program of a case on `input` in a cell limited by `limit`, handing its stdout to `take_output` as it runs.
+///
+/// Gives its usage, what `take_output` makes of the stdout, its stderr, and the status told by
+/// the usage alone, which is `None` if the program quitted well and its output is to be checked.
+pub(crate) fn run_user_program<T, F>(
+    program: &CompiledProgram,
+    input: String,
+    limit: &LimitConfig,
+    take_output: F,
+) -> Result<(ProcessBio, T, String, Option<JudgeStatus>)>
+where
+    F: FnOnce(ChildStdout) -> Result<T>,
+{
+    let path = fs::canonicalize(&program.path)?
+        .to_string_lossy()
+        .to_string();
+
+    let mut command = Command::new(get_path_of_tankcell())
+        .arg(path)
+        .arg(format!("-m {}", limit.memory_limit))
+        .arg(format!("-t {}", limit.time_limit))
+        .args(program.cell_args())
+        .arg("--")
+        .args(program.run_args(limit))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let probe = ProcessProbe::new(command.id())?;
+    let waiter = thread::spawn(move || probe.watching());
+
+    let mut cin = command.stdin.take().unwrap();
+    let errout = read_in_background(command.stderr.take().unwrap());
+    // output is taken while the program is writing it, so input goes in another thread.
+    // the program may quit without reading all of it.
+    thread::spawn(move || {
+        let _ = cin.write_all(input.as_bytes()).and_then(|_| cin.flush());
+    });
+
+    let output = take_output(command.stdout.take().unwrap())?;
+
+    let probe_res = waiter.join().unwrap();
+    let errout = String::from_utf8_lossy(&errout.join().unwrap()?).to_string();
+
+    let status = if probe_res.get_time_usage() >= limit.time_limit {
+        Some(JudgeStatus::TimeLimitExceeded)
+    } else if probe_res.get_peak_memory() >= limit.memory_limit * 1024 {
+        Some(JudgeStatus::MemoryLimitExceeded)
+    } else if errout.find("bad_alloc").is_some() {
+        // fix: struct like vector which does not allocate memory gradually
+        // may touch the wall when memory is still below the limit
+        // even we give two times more of it.
+        Some(JudgeStatus::MemoryLimitExceeded)
+    } else if probe_res.get_status() != 0 {
+        Some(JudgeStatus::RuntimeError)
+    } else {
+        None
+    };
+
+    Ok((probe_res, output, errout, status))
+}
+
+/// Wait for a jury program to quit and tell whether it failed, that is, crashed or exceeded its limit.
+///
+/// CPU time is limited by the cell, but a jury program blocking forever would never hit it,
+/// so it is killed once it has lived twice as long as its time limit.
+pub(crate) fn jury_program_failed(probe: ProcessProbe, limit: &LimitConfig) -> bool {
+    let pid = probe.get_pid();
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let _ = sender.send(probe.watching());
+    });
+
+    let (probe_res, killed) =
+        match receiver.recv_timeout(Duration::from_millis(limit.time_limit * 2)) {
+            Ok(probe_res) => (probe_res, false),
+            Err(_) => {
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                }
+                (receiver.recv().unwrap(), true)
+            }
+        };
+
+    killed || exceeded_jury_limit(&probe_res, limit)
+}
+
+/// Tell whether a jury program that has quitted crashed or exceeded its limit.
+pub(crate) fn exceeded_jury_limit(probe_res: &ProcessBio, limit: &LimitConfig) -> bool {
+    probe_res.get_time_usage() >= limit.time_limit
+        || probe_res.get_peak_memory() >= limit.memory_limit * 1024
+        || probe_res.get_status() != 0
+}
+
+/// Verdict in the first line of the response of an interactor or a manager, named `name`.
+/// One giving nothing known is as broken as a crashed one, and what it gives is the message.
+pub(crate) fn jury_verdict(name: &str, response: &str) -> (JudgeStatus, Option<String>) {
+    if response.trim().is_empty() {
+        return (
+            JudgeStatus::JudgementFailed,
+            Some(format!("{} gives no response", name)),
+        );
+    }
+    match response.lines().next().unwrap().trim() {
+        "same" => (JudgeStatus::Accept, None),
+        "different" => (JudgeStatus::WrongAnswer, None),
+        "pattern_different" => (JudgeStatus::PresentationError, None),
+        _ => (
+            JudgeStatus::JudgementFailed,
+            Some(format!("{} gives unknown result: {}", name, response.trim())),
+        ),
+    }
+}
+
+/// Read a pipe to its end in another thread, so that a full pipe never blocks the process writing it.
+pub(crate) fn read_in_background<R>(mut reader: R) -> JoinHandle<std::io::Result<Vec<u8>>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{compile::prepare_jury_program, problem::DEFAULT_CHECKER_LIMIT};
+    use tempfile::TempDir;
+
+    /// Jury programs, interactors or managers, that fail after the user program quits.
+    const FAILING_JURY_SOURCES: [&str; 2] = [
+        // hangs
+        "#include <cstdio>\n#include <unistd.h>\nint main(){puts(\"1\");fflush(stdout);while(1)sleep(1);}",
+        // crashes
+        "#include <cstdio>\nint main(){puts(\"1\");fflush(stdout);while(getchar()!=EOF);return 1;}",
+    ];
+
+    /// Build a C++ program from `src`, saved as `name` in `dir`.
+    fn program_from_source(dir: &TempDir, name: &str, src: &str) -> Result<CompiledProgram> {
+        let path = dir.path().join(format!("{}.cpp", name));
+        fs::write(&path, src)?;
+        prepare_jury_program(&path.to_string_lossy())
+    }
+
+    #[test]
+    fn normal_large_output() -> Result<()> {
+        // far more output than that kept in the result
+        let dir = TempDir::new()?;
+        let program = program_from_source(
+            &dir,
+            "solution",
+            "#include <cstdio>\nint main(){for(int i=0;i<1000000;i++)printf(\"%d\\n\",i);}",
+        )?;
+        let answer: String = (0..1000000).map(|f| format!("{}\n", f)).collect();
+        let input_file = dir.path().join("1.in");
+        fs::write(&input_file, "")?;
+
+        for (tail, status) in [("", JudgeStatus::Accept), ("1000000\n", JudgeStatus::WrongAnswer)] {
+            let answer_file = dir.path().join("1.ans");
+            fs::write(&answer_file, format!("{}{}", answer, tail))?;
+            let result = launch_normal_case_judge(
+                program.clone(),
+                &input_file.to_string_lossy(),
+                &answer_file.to_string_lossy(),
+                LimitConfig {
+                    time_limit: 2000,
+                    memory_limit: 256,
+                },
+                &ComparisionModeConfig::Line,
+            )?;
+            assert_eq!(result.status, status);
+            assert_eq!(result.stdout.unwrap().len(), normal::KEPT_OUTPUT_BYTES);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn interactive_accept() -> Result<()> {
         let judge = InteractiveJudge::new(
@@ -130,10 +492,409 @@ mod tests {
                 time_limit: 1000,
                 memory_limit: 256,
             },
-            "../test_dep/interactive/interactor".into(),
+            CompiledProgram::new("../test_dep/interactive/interactor".into()),
+            DEFAULT_CHECKER_LIMIT,
+            None,
+            None,
+        );
+        let result = judge.judge()?;
+        assert_eq!(result.status, JudgeStatus::Accept);
+        assert_eq!(result.message, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_interactor_failed() -> Result<()> {
+        let dir = TempDir::new()?;
+        for (index, src) in FAILING_JURY_SOURCES.iter().enumerate() {
+            let judge = InteractiveJudge::new(
+                CompiledProgram::new("../test_dep/checker/solution".into()),
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("interactor{}", index), src)?,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                None,
+                None,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::JudgementFailed);
+            assert_eq!(result.message, None);
+            // killed on its own time limit rather than that of the whole interaction
+            assert!(result.wall_time.unwrap() < 2000);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_exit_order() -> Result<()> {
+        let dir = TempDir::new()?;
+        let cases = [
+            // interactor rejects at once, and the program crashes on EOF then
+            (
+                "#include <cstdio>\nint main(){fputs(\"different\\n\",stderr);}",
+                "#include <cstdio>\n#include <cstdlib>\nint main(){int x;if(scanf(\"%d\",&x)!=1)abort();}",
+                "interactor exited before program",
+            ),
+            // the program quits without answering the query
+            (
+                "#include <cstdio>\nint main(){int x;puts(\"1\");fflush(stdout);fputs(scanf(\"%d\",&x)==1?\"same\\n\":\"different\\n\",stderr);}",
+                "int main(){}",
+                "program exited before interactor",
+            ),
+        ];
+        for (index, (interactor_src, program_src, message)) in cases.iter().enumerate() {
+            let judge = InteractiveJudge::new(
+                program_from_source(&dir, &format!("solution{}", index), program_src)?,
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("interactor{}", index), interactor_src)?,
+                DEFAULT_CHECKER_LIMIT,
+                None,
+                None,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::WrongAnswer);
+            assert_eq!(result.message.as_deref(), Some(*message));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_unknown_verdict() -> Result<()> {
+        let dir = TempDir::new()?;
+        let interactors = [
+            ("#include <cstdio>\nint main(){}", "interactor gives no response"),
+            (
+                "#include <cstdio>\nint main(){fputs(\"maybe\\n\",stderr);}",
+                "interactor gives unknown result: maybe",
+            ),
+        ];
+        for (index, (src, message)) in interactors.iter().enumerate() {
+            let judge = InteractiveJudge::new(
+                CompiledProgram::new("../test_dep/checker/solution".into()),
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("interactor{}", index), src)?,
+                DEFAULT_CHECKER_LIMIT,
+                None,
+                None,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::JudgementFailed);
+            assert_eq!(result.message.as_deref(), Some(*message));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_run_eof() -> Result<()> {
+        // interactor takes what the run gives, followed by the notice of its end
+        let dir = TempDir::new()?;
+        let interactor = program_from_source(
+            &dir,
+            "interactor",
+            "#include <iostream>\n#include <string>\nint main(){std::string a,b;std::cin>>a;if(a!=\"1\")return 1;std::cin>>b;if(b!=\"eof\")return 1;std::cerr<<\"same\"<<std::endl;}",
+        )?;
+        let cases = [
+            ("#include <cstdio>\nint main(){puts(\"1\");}", JudgeStatus::Accept),
+            // interactor fails on the crashed run, which is still to blame
+            ("#include <cstdlib>\nint main(){abort();}", JudgeStatus::RuntimeError),
+        ];
+        for (index, (src, status)) in cases.iter().enumerate() {
+            let judge = InteractiveJudge::new(
+                program_from_source(&dir, &format!("solution{}", index), src)?,
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                interactor.clone(),
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                None,
+                Some(2),
+            );
+            let result = judge.judge()?;
+            assert_eq!(&result.status, status);
+            assert_eq!(result.message, None);
+            // interactor is not left waiting for the run until it is killed
+            assert!(result.wall_time.unwrap() < 1000);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_next_run_order() -> Result<()> {
+        // interactor asks for the next run right after writing plenty to the current one,
+        // and a run sums up numbers until 0 or EOF.
+        let dir = TempDir::new()?;
+        let interactor = program_from_source(
+            &dir,
+            "interactor",
+            "#include <iostream>\n#include <string>\nint main(){std::string a,b,c,d;for(int i=0;i<10000;i++)std::cout<<\"1\\n\";std::cout<<std::flush;std::cerr<<\"next_run\"<<std::endl;std::cin>>a>>b;std::cout<<\"2 0\"<<std::endl;std::cin>>c>>d;bool ok=a==\"10000\"&&b==\"eof\"&&c==\"2\"&&d==\"eof\";std::cerr<<(ok?\"same\":\"different\")<<std::endl;}",
+        )?;
+        let program = program_from_source(
+            &dir,
+            "solution",
+            "#include <cstdio>\nint main(){int x,s=0;while(scanf(\"%d\",&x)==1&&x)s+=x;printf(\"%d\\n\",s);}",
+        )?;
+        let judge = InteractiveJudge::new(
+            program,
+            None,
+            LimitConfig {
+                time_limit: 1000,
+                memory_limit: 256,
+            },
+            interactor,
+            DEFAULT_CHECKER_LIMIT,
+            None,
+            Some(2),
         );
         let result = judge.judge()?;
-        println!("{:?}", result.status);
+        assert_eq!(result.status, JudgeStatus::Accept);
+        assert_eq!(result.message, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn communication_accept() -> Result<()> {
+        let judge = CommunicationJudge::new(
+            vec![CompiledProgram::new("../test_dep/communication/solution".into()); 2],
+            None,
+            LimitConfig {
+                time_limit: 1000,
+                memory_limit: 256,
+            },
+            CompiledProgram::new("../test_dep/communication/manager".into()),
+            DEFAULT_CHECKER_LIMIT,
+            CommunicationWiring::Star,
+        );
+        let result = judge.judge()?;
+        assert_eq!(result.status, JudgeStatus::Accept);
+        assert_eq!(result.message, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn communication_roles() -> Result<()> {
+        // manager expects 21 from process 0 and 42 from process 1, which never read their index
+        let dir = TempDir::new()?;
+        let mut programs = Vec::new();
+        for (role, src) in &[
+            ("encoder", "#include <cstdio>\nint main(){int x;scanf(\"%d\",&x);printf(\"%d\\n\",x);}"),
+            ("decoder", "#include <cstdio>\nint main(){int x;scanf(\"%d\",&x);printf(\"%d\\n\",x*2);}"),
+        ] {
+            programs.push(program_from_source(&dir, role, src)?);
+        }
+
+        for (programs, status) in [
+            (programs.clone(), JudgeStatus::Accept),
+            (vec![programs[0].clone(); 2], JudgeStatus::WrongAnswer),
+        ] {
+            let judge = CommunicationJudge::new(
+                programs,
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                CompiledProgram::new("../test_dep/communication/manager".into()),
+                DEFAULT_CHECKER_LIMIT,
+                CommunicationWiring::Star,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, status);
+            assert_eq!(result.message, None);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn communication_manager_failed() -> Result<()> {
+        let dir = TempDir::new()?;
+        for (index, src) in FAILING_JURY_SOURCES.iter().enumerate() {
+            let judge = CommunicationJudge::new(
+                vec![CompiledProgram::new("../test_dep/checker/solution".into())],
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("manager{}", index), src)?,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                CommunicationWiring::Chain,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::JudgementFailed);
+            assert_eq!(result.message, None);
+            // killed on its own time limit rather than that of the whole communication
+            assert!(result.wall_time.unwrap() < 2000);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn communication_user_crashed() -> Result<()> {
+        // a crashed process is to blame, even if the manager fails on it then
+        let dir = TempDir::new()?;
+        let program =
+            program_from_source(&dir, "solution", "#include <cstdlib>\nint main(){abort();}")?;
+        for (index, src) in FAILING_JURY_SOURCES.iter().enumerate() {
+            let judge = CommunicationJudge::new(
+                vec![program.clone()],
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("manager{}", index), src)?,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                CommunicationWiring::Chain,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::RuntimeError);
+            assert_eq!(result.message, None);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn communication_unknown_verdict() -> Result<()> {
+        let dir = TempDir::new()?;
+        let managers = [
+            ("#include <cstdio>\nint main(){}", "manager gives no response"),
+            (
+                "#include <cstdio>\nint main(){fputs(\"maybe\\n\",stderr);}",
+                "manager gives unknown result: maybe",
+            ),
+        ];
+        for (index, (src, message)) in managers.iter().enumerate() {
+            let judge = CommunicationJudge::new(
+                vec![CompiledProgram::new("../test_dep/checker/solution".into())],
+                None,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                program_from_source(&dir, &format!("manager{}", index), src)?,
+                DEFAULT_CHECKER_LIMIT,
+                CommunicationWiring::Chain,
+            );
+            let result = judge.judge()?;
+            assert_eq!(result.status, JudgeStatus::JudgementFailed);
+            assert_eq!(result.message.as_deref(), Some(*message));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn special_with_answer() -> Result<()> {
+        // the output is the same as input, so only the answer tells it wrong
+        let checker = prepare_jury_program("../test_dep/checker/answer_checker.cpp")?;
+        for (answer, status) in &[
+            ("../test_dep/checker/1.ans", JudgeStatus::Accept),
+            ("../test_dep/checker/2.ans", JudgeStatus::WrongAnswer),
+        ] {
+            let result = launch_special_case_judge(
+                CompiledProgram::new("../test_dep/checker/solution".into()),
+                "../test_dep/checker/1.in",
+                Some(answer),
+                &checker,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+                DEFAULT_CHECKER_LIMIT,
+            )?;
+            assert_eq!(&result.status, status);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn special_unknown_verdict() -> Result<()> {
+        let dir = TempDir::new()?;
+        let checker = program_from_source(
+            &dir,
+            "checker",
+            "#include <cstdio>\nint main(){puts(\"maybe\");}",
+        )?;
+        let result = launch_special_case_judge(
+            CompiledProgram::new("../test_dep/checker/solution".into()),
+            "../test_dep/checker/1.in",
+            Some("../test_dep/checker/1.ans"),
+            &checker,
+            LimitConfig {
+                time_limit: 1000,
+                memory_limit: 256,
+            },
+            DEFAULT_CHECKER_LIMIT,
+        )?;
+        assert_eq!(result.status, JudgeStatus::JudgementFailed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_with_answer() -> Result<()> {
+        let checker = compile_checker_script(
+            r#"
+            let x = output.ri64();
+            if x == answer.ri64() {
+                #{ verdict: "same", score: 1.0 }
+            } else {
+                #{ verdict: "different", score: 0, message: "got " + x }
+            }
+            "#,
+        )?;
+        for (answer, status) in &[
+            ("../test_dep/checker/1.ans", JudgeStatus::Accept),
+            ("../test_dep/checker/2.ans", JudgeStatus::WrongAnswer),
+        ] {
+            let result = launch_script_case_judge(
+                CompiledProgram::new("../test_dep/checker/solution".into()),
+                "../test_dep/checker/1.in",
+                Some(answer),
+                &checker,
+                LimitConfig {
+                    time_limit: 1000,
+                    memory_limit: 256,
+                },
+            )?;
+            assert_eq!(&result.status, status);
+        }
 
         Ok(())
     }
@@ -1,20 +1,35 @@
-use std::io::{Read, Write};
-use std::{
-    fs,
-    path::Path,
-    process::{Command, Stdio},
-};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
 
-use super::{get_path_of_tankcell, Judge};
+use super::{run_user_program, Judge};
 use crate::{
-    compare::ComparisionMode, compile::CompiledProgram, problem::LimitConfig, error::Result,
-    probe::ProcessProbe, JudgeResult, JudgeStatus,
+    compare::ComparisionMode, compile::CompiledProgram, error::Result, problem::LimitConfig,
+    JudgeResult,
 };
 
+/// Bytes of output kept in the result, the rest is only compared.
+pub(super) const KEPT_OUTPUT_BYTES: usize = 64 << 10;
+
+/// Reader keeping the first `KEPT_OUTPUT_BYTES` of what is read through it.
+struct PrefixKeeper<R> {
+    inner: R,
+    kept: Vec<u8>,
+}
+
+impl<R: Read> Read for PrefixKeeper<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        let room = KEPT_OUTPUT_BYTES.saturating_sub(self.kept.len());
+        self.kept.extend_from_slice(&buf[..len.min(room)]);
+        Ok(len)
+    }
+}
+
 pub struct NormalJudge {
     program: CompiledProgram,
     input: String,
-    answer: String,
+    /// path of answer file, which is compared with output as they are read.
+    answer_file: String,
     limit: LimitConfig,
     comparation: Box<dyn ComparisionMode>,
 }
@@ -23,7 +38,7 @@ impl NormalJudge {
     pub fn new(
         program: CompiledProgram,
         input: String,
-        answer: String,
+        answer_file: String,
         memory_limit: u64,
         time_limit: u64,
         comparation: Box<dyn ComparisionMode>,
@@ -31,7 +46,7 @@ impl NormalJudge {
         Self {
             program,
             input,
-            answer,
+            answer_file,
             limit: LimitConfig {
                 time_limit: time_limit,
                 memory_limit: memory_limit,
@@ -43,81 +58,42 @@ impl NormalJudge {
 
 impl Judge for NormalJudge {
     fn judge(self) -> Result<JudgeResult> {
-        let path = Path::new(&self.program.path);
-
-        let path = fs::canonicalize(path)
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
+        let mut answer = BufReader::new(File::open(&self.answer_file)?);
+        let comparation = self.comparation;
+        let (probe_res, (comparision, output), errout, judge_status) =
+            run_user_program(&self.program, self.input, &self.limit, |cout| {
+                let mut output = PrefixKeeper {
+                    inner: cout,
+                    kept: Vec::new(),
+                };
+                let comparision =
+                    comparation.compare_stream(&mut answer, &mut BufReader::new(&mut output))?;
+                // what is left after a difference is read on, so that the program is never blocked.
+                io::copy(&mut output, &mut io::sink())?;
+                Ok((comparision, output.kept))
+            })?;
+        let output = String::from_utf8_lossy(&output).to_string();
 
-        let mut command = Command::new(get_path_of_tankcell())
-            .arg(path)
-            .arg(format!("-m {}", self.limit.memory_limit))
-            .arg(format!("-t {}", self.limit.time_limit))
-            .arg("-p minimum")
-            .arg("--")
-            .args(self.program.args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
-
-        let pid = command.id();
-        let probe = ProcessProbe::new(pid)?;
-
-        let cin = command.stdin.as_mut().ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stdin",
-        ))?;
-        cin.write_all(self.input.into_bytes().as_slice())?;
-        cin.flush()?;
-        drop(cin);
-        drop(command.stdin);
-
-        let cout = command.stdout.as_mut().ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stdout",
-        ))?;
-        let cerr = command.stderr.as_mut().ok_or(std::io::Error::new(
-            std::io::ErrorKind::BrokenPipe,
-            "failed to open stderr",
-        ))?;
-        let probe_res = probe.watching();
-
-        let mut output = String::new();
-        cout.read_to_string(&mut output)?;
-        let mut errout = String::new();
-        cerr.read_to_string(&mut errout)?;
-
-        // check result
-        let mut judge_status = if probe_res.get_time_usage() >= self.limit.time_limit {
-            JudgeStatus::TimeLimitExceeded
-        } else if probe_res.get_peak_memory() >= self.limit.memory_limit * 1024 {
-            JudgeStatus::MemoryLimitExceeded
-        } else if errout.find("bad_alloc").is_some() {
-            // fix: struct like vector which does not allocate memory gradually
-            // may touch the wall when memory is still below the limit
-            // even we give two times more of it.
-            JudgeStatus::MemoryLimitExceeded
-        } else if probe_res.get_status() != 0 {
-            JudgeStatus::RuntimeError
-        } else {
-            JudgeStatus::Uncertain
+        let (judge_status, message) = match judge_status {
+            Some(judge_status) => (judge_status, None),
+            None => {
+                let message = comparision.difference().map(|f| f.to_string());
+                (comparision.into(), message)
+            }
         };
 
-        if let JudgeStatus::Uncertain = judge_status {
-            judge_status = self.comparation.compare(&self.answer, &output).into();
-        }
-
         let judge_result = JudgeResult {
             status: judge_status,
             time: probe_res.get_time_usage().into(),
             memory: probe_res.get_peak_memory().into(),
+            interactor_time: None,
+            wall_time: None,
             stdin: None,
             stdout: output.into(),
             stderr: errout.into(),
+            transcript: None,
+            message,
+            score: None,
         };
 
         Ok(judge_result)
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+use rhai::AST;
+
+use super::{script::run_checker_script, special::run_checker, Judge};
+use crate::{
+    compare::ComparisionMode,
+    compile::CompiledProgram,
+    error::{Error, Result},
+    problem::LimitConfig,
+    JudgeResult, JudgeStatus,
+};
+
+/// How an output is checked when there is no program to run.
+pub enum OutputCheck {
+    Comparision(Box<dyn ComparisionMode>),
+    Checker(CompiledProgram, LimitConfig),
+    Script(AST),
+}
+
+pub struct OutputOnlyJudge<'a> {
+    output: Option<String>,
+    input: String,
+    answer: Option<String>,
+    check: &'a OutputCheck,
+}
+
+impl<'a> OutputOnlyJudge<'a> {
+    pub fn new(
+        output: Option<String>,
+        input: String,
+        answer: Option<String>,
+        check: &'a OutputCheck,
+    ) -> Self {
+        Self {
+            output,
+            input,
+            answer,
+            check,
+        }
+    }
+}
+
+impl<'a> Judge for OutputOnlyJudge<'a> {
+    fn judge(self) -> Result<JudgeResult> {
+        let mut message = None;
+        let mut score = None;
+        let judge_status = match &self.output {
+            // a case without output submitted is simply wrong
+            None => JudgeStatus::WrongAnswer,
+            Some(output) => match self.check {
+                OutputCheck::Comparision(comparation) => {
+                    let comparision = comparation.compare(self.answer.as_ref().unwrap(), output);
+                    message = comparision.difference().map(|f| f.to_string());
+                    comparision.into()
+                }
+                OutputCheck::Checker(checker, checker_limit) => run_checker(
+                    checker,
+                    checker_limit,
+                    &self.input,
+                    output,
+                    self.answer.as_deref(),
+                )?,
+                OutputCheck::Script(checker) => {
+                    let verdict =
+                        run_checker_script(checker, &self.input, output, self.answer.as_deref());
+                    message = verdict.message;
+                    score = verdict.score;
+                    verdict.status
+                }
+            },
+        };
+
+        let judge_result = JudgeResult {
+            status: judge_status,
+            time: None,
+            memory: None,
+            interactor_time: None,
+            wall_time: None,
+            stdin: None,
+            stdout: self.output,
+            stderr: None,
+            transcript: None,
+            message,
+            score,
+        };
+        Ok(judge_result)
+    }
+}
+
+/// Read outputs submitted as a directory or a zip archive, keyed by their file names.
+///
+/// Only the name of a file is kept, so outputs may be put in subdirectories freely,
+/// as long as no two of them share a name.
+pub fn collect_outputs(path: &str) -> Result<HashMap<String, String>> {
+    let mut outputs = HashMap::new();
+    let path = Path::new(path);
+
+    if path.is_dir() {
+        collect_directory(path, &mut outputs)?;
+    } else {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = match Path::new(file.name()).file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+            let mut output = Vec::new();
+            file.read_to_end(&mut output)?;
+            insert_output(&mut outputs, name, &output)?;
+        }
+    }
+
+    Ok(outputs)
+}
+
+fn collect_directory(path: &Path, outputs: &mut HashMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_directory(&entry.path(), outputs)?;
+        } else {
+            let output = fs::read(entry.path())?;
+            insert_output(
+                outputs,
+                entry.file_name().to_string_lossy().to_string(),
+                &output,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep an output, refusing another one of the same name, since which is meant for the case is unknown.
+fn insert_output(outputs: &mut HashMap<String, String>, name: String, output: &[u8]) -> Result<()> {
+    if outputs.contains_key(&name) {
+        return Err(Error::Data(format!("more than one output named `{}`", name)));
+    }
+    outputs.insert(name, String::from_utf8_lossy(output).to_string());
+
+    Ok(())
+}
@@ -212,9 +212,14 @@ impl RemoteSniffer for OpentrainsJudgeSniffer {
                 status: status,
                 time: None,
                 memory: None,
+                interactor_time: None,
+                wall_time: None,
                 stdin:None,
                 stdout: None,
                 stderr: None,
+                transcript: None,
+                message: None,
+                score: None,
             });
         } else if let Some(_sel) = sel.find(predicate::Name("pre")).next() {
             // TODO: add CE info
@@ -222,9 +227,14 @@ impl RemoteSniffer for OpentrainsJudgeSniffer {
                 status: JudgeStatus::ComplierError,
                 time: None,
                 memory: None,
+                interactor_time: None,
+                wall_time: None,
                 stdin:None,
                 stdout: None,
                 stderr: None,
+                transcript: None,
+                message: None,
+                score: None,
             });
         }
 
@@ -0,0 +1,188 @@
+use std::io::Read;
+
+use rhai::{Dynamic, Engine, ImmutableString, Map, Scope, AST};
+
+use super::{run_user_program, Judge};
+use crate::{
+    byte_scanner::{ByteScanner, ByteScannerScriptBinder, ScriptInject},
+    compile::CompiledProgram,
+    error::Result,
+    problem::LimitConfig,
+    JudgeResult, JudgeStatus,
+};
+
+/// Operations a checker script may take on one case, so that a dead loop does not hang judging.
+const MAX_SCRIPT_OPERATIONS: u64 = 100_000_000;
+
+/// What a checker script tells about an output.
+pub struct ScriptVerdict {
+    pub status: JudgeStatus,
+    pub score: Option<f64>,
+    pub message: Option<String>,
+}
+
+/// Compile checker script, reporting its syntax errors early.
+pub fn compile_checker_script(script: &str) -> Result<AST> {
+    Ok(Engine::new().compile(script)?)
+}
+
+fn status_of(verdict: &str) -> Option<JudgeStatus> {
+    // the same words as what a checker writes
+    match verdict {
+        "same" => Some(JudgeStatus::Accept),
+        "different" => Some(JudgeStatus::WrongAnswer),
+        "pattern_different" => Some(JudgeStatus::PresentationError),
+        _ => None,
+    }
+}
+
+/// Run checker script on an output.
+///
+/// Scanners `input`, `output` and `answer` are given to the script, the last of which reads nothing if there is no answer.
+/// The script gives `true`/`false`, a verdict like a checker, or a map `#{ verdict: ..., score: ..., message: ... }`.
+pub(super) fn run_checker_script(
+    checker: &AST,
+    input: &str,
+    output: &str,
+    answer: Option<&str>,
+) -> ScriptVerdict {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    let mut scope = Scope::new();
+
+    let binder = |data: &str| ByteScannerScriptBinder::new(ByteScanner::from_bytes(data.as_bytes().to_vec()));
+    let output_binder = binder(output);
+    output_binder.inject_engine(&mut engine);
+    binder(input).inject_scope(&mut scope, "input");
+    output_binder.inject_scope(&mut scope, "output");
+    binder(answer.unwrap_or_default()).inject_scope(&mut scope, "answer");
+    scope.push("has_answer", answer.is_some());
+
+    let failed = |message: String| ScriptVerdict {
+        status: JudgeStatus::JudgementFailed,
+        score: None,
+        message: Some(message),
+    };
+
+    let result = match engine.eval_ast_with_scope::<Dynamic>(&mut scope, checker) {
+        Ok(result) => result,
+        Err(err) => return failed(format!("checker script failed: {}", err)),
+    };
+
+    let (verdict, score, message) = if let Some(accepted) = result.clone().try_cast::<bool>() {
+        let verdict = if accepted { "same" } else { "different" };
+        (Some(Dynamic::from(ImmutableString::from(verdict))), None, None)
+    } else if let Some(map) = result.clone().try_cast::<Map>() {
+        (
+            map.get("verdict").cloned(),
+            map.get("score").cloned(),
+            map.get("message").cloned(),
+        )
+    } else {
+        (Some(result), None, None)
+    };
+
+    let status = match verdict
+        .and_then(|f| f.try_cast::<ImmutableString>())
+        .and_then(|f| status_of(f.as_str()))
+    {
+        Some(status) => status,
+        None => return failed("checker script gives unknown result".into()),
+    };
+    let score = score.and_then(|f| {
+        f.clone()
+            .try_cast::<f64>()
+            .or_else(|| f.try_cast::<i64>().map(|f| f as f64))
+    });
+
+    // errors met when reading output are a good hint if the script says nothing
+    let message = message
+        .and_then(|f| f.try_cast::<ImmutableString>())
+        .map(|f| f.to_string())
+        .or_else(|| {
+            let errors = scope
+                .get_value::<ByteScannerScriptBinder>("output")
+                .map(|f| f.get_err())
+                .unwrap_or_default();
+            if errors.is_empty() {
+                None
+            } else {
+                Some(errors.join("\n"))
+            }
+        });
+
+    ScriptVerdict {
+        status,
+        score,
+        message,
+    }
+}
+
+pub struct ScriptJudge<'a> {
+    program: CompiledProgram,
+    input: String,
+    answer: Option<String>,
+    limit: LimitConfig,
+    checker: &'a AST,
+}
+
+impl<'a> ScriptJudge<'a> {
+    pub fn new(
+        program: CompiledProgram,
+        input: String,
+        answer: Option<String>,
+        memory_limit: u64,
+        time_limit: u64,
+        checker: &'a AST,
+    ) -> Self {
+        Self {
+            program,
+            input,
+            answer,
+            limit: LimitConfig {
+                memory_limit,
+                time_limit,
+            },
+            checker,
+        }
+    }
+}
+
+impl<'a> Judge for ScriptJudge<'a> {
+    fn judge(self) -> Result<JudgeResult> {
+        let (probe_res, output, errout, judge_status) = run_user_program(
+            &self.program,
+            self.input.clone(),
+            &self.limit,
+            |mut cout| {
+                let mut output = String::new();
+                cout.read_to_string(&mut output)?;
+                Ok(output)
+            },
+        )?;
+
+        let verdict = match judge_status {
+            Some(status) => ScriptVerdict {
+                status,
+                score: None,
+                message: None,
+            },
+            None => run_checker_script(self.checker, &self.input, &output, self.answer.as_deref()),
+        };
+
+        let judge_result = JudgeResult {
+            status: verdict.status,
+            time: probe_res.get_time_usage().into(),
+            memory: probe_res.get_peak_memory().into(),
+            interactor_time: None,
+            wall_time: None,
+            stdin: None,
+            stdout: output.into(),
+            stderr: errout.into(),
+            transcript: None,
+            message: verdict.message,
+            score: verdict.score,
+        };
+        Ok(judge_result)
+    }
+}
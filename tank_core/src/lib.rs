@@ -7,18 +7,28 @@ pub mod compile;
 pub mod lint;
 mod container;
 pub mod byte_scanner;
+pub mod transcript;
+
+use transcript::Transcript;
 
 #[derive(Debug)]
 pub struct JudgeResult {
     pub status: JudgeStatus,
     pub time: Option<u64>,
     pub memory: Option<u64>,
+    pub interactor_time: Option<u64>,
+    pub wall_time: Option<u64>,
     pub stdin:Option<String>,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
+    pub transcript: Option<Transcript>,
+    /// where the output first differs from the answer, for wrong answers found by comparison.
+    pub message: Option<String>,
+    /// part of the full score given by checker script, usually between 0 and 1.
+    pub score: Option<f64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum JudgeStatus {
     Uncertain,
     Accept,
@@ -30,4 +40,5 @@ pub enum JudgeStatus {
     ComplierError,
     ComplierLimitExceeded,
     RuntimeError,
+    JudgementFailed,
 }
@@ -17,6 +17,10 @@ impl ProcessProbe {
         Ok(Self { pid })
     }
 
+    pub fn get_pid(&self) -> u32 {
+        self.pid
+    }
+
     #[allow(dead_code)]
     pub fn get_stat(&self) -> ProcessStat {
         let content1 = fs::read_to_string(format!("/proc/{}/stat", self.pid)).unwrap();
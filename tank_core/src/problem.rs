@@ -1,13 +1,22 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    compare::{ComparisionMode, GlobalCompare, LineCompare, ValueCompare},
-    compile::CompiledProgram,
+    compare::{
+        line_pattern, CaseInsensitiveValueCompare, ComparisionMode, FloatCompare, GlobalCompare,
+        LineCompare, NewlineNormalized, RegexCompare, UnorderedLinesCompare,
+        UnorderedTokensCompare, ValueCompare,
+    },
+    compile::{prepare_jury_program, CompiledProgram, CompilerOptions},
     error::{Error, Result},
-    judge::{launch_interactive_case_judge, launch_normal_case_judge, launch_special_case_judge},
+    judge::{
+        collect_outputs, launch_communication_case_judge, launch_interactive_case_judge,
+        compile_checker_script, launch_normal_case_judge, launch_output_only_case_judge,
+        launch_script_case_judge, launch_special_case_judge, OutputCheck,
+    },
     lint::DataLinter,
     JudgeResult,
 };
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -18,6 +27,12 @@ pub struct LimitConfig {
     #[serde(rename = "memoryLimit")]
     pub memory_limit: u64,
 }
+
+/// Limit for checkers and interactors when `checkerLimit` is not given.
+pub const DEFAULT_CHECKER_LIMIT: LimitConfig = LimitConfig {
+    time_limit: 10000,
+    memory_limit: 1024,
+};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CaseConfig {
     #[serde(rename = "inputFile")]
@@ -31,6 +46,20 @@ pub enum ComparisionModeConfig {
     Full,
     Line,
     Value,
+    Float {
+        #[serde(rename = "absEps")]
+        abs_eps: f64,
+        #[serde(rename = "relEps")]
+        rel_eps: f64,
+    },
+    /// lines in any order.
+    UnorderedLines,
+    /// lines in order, and tokens of each line in any order.
+    UnorderedTokens,
+    /// tokens compared regardless of their case.
+    ValueIgnoreCase,
+    /// each line of output matches the regex in the same line of answer.
+    Regex,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,18 +71,71 @@ pub enum JudgeModeConfig {
     Special {
         checker: String,
     },
+    /// checked by a Rhai script written here, instead of a checker program.
+    Script {
+        checker: String,
+    },
     Interactive {
         interactor: String,
         has_input: bool,
+        transcript_dir: Option<String>,
+        max_runs: Option<u64>,
     },
+    Communication {
+        manager: String,
+        /// processes all running the submission, as a problem takes a single program.
+        processes: u64,
+        wiring: CommunicationWiring,
+        has_input: bool,
+    },
+    Grader {
+        #[serde(rename = "comparisionMode")]
+        comparision_mode: ComparisionModeConfig,
+        /// graders keyed by the id of language, like `cpp` or `python`.
+        graders: HashMap<String, GraderConfig>,
+    },
+}
+
+/// Files of jury compiled or run together with the submission of one language.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraderConfig {
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub headers: Vec<String>,
 }
 
+/// How processes of user program are connected in communication mode.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum CommunicationWiring {
+    /// Every process talks with manager alone, through a pair of fifos given to manager.
+    Star,
+    /// Manager's output goes through all processes in order, and comes back to its input.
+    Chain,
+}
+
+/// Line endings of Windows are never regarded as a difference, whichever the mode is.
 impl Into<Box<dyn ComparisionMode>> for &ComparisionModeConfig {
     fn into(self) -> Box<dyn ComparisionMode> {
         match self {
-            ComparisionModeConfig::Full => Box::new(GlobalCompare {}),
-            ComparisionModeConfig::Line => Box::new(LineCompare {}),
-            ComparisionModeConfig::Value => Box::new(ValueCompare {}),
+            ComparisionModeConfig::Full => Box::new(NewlineNormalized(GlobalCompare {})),
+            ComparisionModeConfig::Line => Box::new(NewlineNormalized(LineCompare {})),
+            ComparisionModeConfig::Value => Box::new(NewlineNormalized(ValueCompare {})),
+            ComparisionModeConfig::Float { abs_eps, rel_eps } => {
+                Box::new(NewlineNormalized(FloatCompare {
+                    abs_eps: *abs_eps,
+                    rel_eps: *rel_eps,
+                }))
+            }
+            ComparisionModeConfig::UnorderedLines => {
+                Box::new(NewlineNormalized(UnorderedLinesCompare {}))
+            }
+            ComparisionModeConfig::UnorderedTokens => {
+                Box::new(NewlineNormalized(UnorderedTokensCompare {}))
+            }
+            ComparisionModeConfig::ValueIgnoreCase => {
+                Box::new(NewlineNormalized(CaseInsensitiveValueCompare {}))
+            }
+            ComparisionModeConfig::Regex => Box::new(NewlineNormalized(RegexCompare {})),
         }
     }
 }
@@ -65,12 +147,17 @@ pub struct ProblemConfig<'a> {
     path: String,
     #[serde(rename = "limitConfig")]
     pub limit_config: LimitConfig,
+    #[serde(rename = "checkerLimit")]
+    pub checker_limit: Option<LimitConfig>,
     #[serde(rename = "judgeMode")]
     pub judge_mode: JudgeModeConfig,
     #[serde(rename = "inputLint")]
     pub input_lint: Option<DataLinter<'a>>,
     #[serde(rename = "answerLint")]
     pub answer_lint: Option<DataLinter<'a>>,
+    /// how submissions are compiled, keyed by language id like `cpp`.
+    #[serde(default)]
+    pub compilers: HashMap<String, CompilerOptions>,
     pub cases: Vec<CaseConfig>,
 }
 
@@ -147,12 +234,46 @@ impl<'a> ProblemConfig<'a> {
             }
         }
 
+        // answers are patterns in regex mode, which had better be valid
+        if let JudgeModeConfig::Normal {
+            comparision_mode: ComparisionModeConfig::Regex,
+        }
+        | JudgeModeConfig::Grader {
+            comparision_mode: ComparisionModeConfig::Regex,
+            ..
+        } = &self.judge_mode
+        {
+            for answerfile_path in self.cases.iter().filter_map(|f| f.answerfile_path.as_ref()) {
+                let answer = fs::read_to_string(self.find_relative_path(answerfile_path))?;
+                for line in answer.lines() {
+                    if let Err(err) = line_pattern(line.trim_end()) {
+                        return Err(Error::Data(format!("{}: {}", answerfile_path, err)));
+                    }
+                }
+            }
+        }
+
         if let JudgeModeConfig::Special { checker } = &self.judge_mode {
             if !Path::new(&self.find_relative_path(checker)).exists() {
                     return Err(Error::NotFound(checker.to_string()));
             }
         }
 
+        if let JudgeModeConfig::Script { checker } = &self.judge_mode {
+            compile_checker_script(checker)?;
+        }
+
+        if let JudgeModeConfig::Grader { graders, .. } = &self.judge_mode {
+            for file in graders
+                .values()
+                .flat_map(|f| f.sources.iter().chain(f.headers.iter()))
+            {
+                if !Path::new(&self.find_relative_path(file)).exists() {
+                    return Err(Error::NotFound(file.to_string()));
+                }
+            }
+        }
+
         Ok(())
     }
     pub fn find_relative_path(&self, path: &str) -> String {
@@ -162,10 +283,42 @@ impl<'a> ProblemConfig<'a> {
             .to_string();
         t
     }
+    /// Grader to build the submission in `language` with, if the problem is in grader mode.
+    /// A submission in a language without grader cannot be judged, as it would be built alone.
+    pub fn grader_of(&self, language: &str) -> Result<Option<GraderConfig>> {
+        if let JudgeModeConfig::Grader { graders, .. } = &self.judge_mode {
+            match graders.get(language) {
+                Some(f) => Ok(Some(GraderConfig {
+                    sources: f.sources.iter().map(|f| self.find_relative_path(f)).collect(),
+                    headers: f.headers.iter().map(|f| self.find_relative_path(f)).collect(),
+                })),
+                None => Err(Error::NotFound(format!("grader of language `{}`", language))),
+            }
+        } else {
+            Ok(None)
+        }
+    }
     pub fn judge(&self, exec: CompiledProgram) -> Result<Vec<JudgeResult>> {
         let mut judge_results = Vec::new();
+        let checker_limit = self.checker_limit.clone().unwrap_or(DEFAULT_CHECKER_LIMIT);
+        // checker script, checker, interactor and manager are compiled once for all cases
+        let checker_script = match &self.judge_mode {
+            JudgeModeConfig::Script { checker } => Some(compile_checker_script(checker)?),
+            _ => None,
+        };
+        let jury_program = match &self.judge_mode {
+            JudgeModeConfig::Special { checker: program }
+            | JudgeModeConfig::Interactive {
+                interactor: program,
+                ..
+            }
+            | JudgeModeConfig::Communication {
+                manager: program, ..
+            } => Some(prepare_jury_program(&self.find_relative_path(program))?),
+            _ => None,
+        };
 
-        for case in self.cases.iter() {
+        for (index, case) in self.cases.iter().enumerate() {
             let judge_result = match &self.judge_mode {
                 JudgeModeConfig::Normal { comparision_mode } => launch_normal_case_judge(
                     exec.clone(),
@@ -175,26 +328,85 @@ impl<'a> ProblemConfig<'a> {
                     self.limit_config.clone(),
                     comparision_mode,
                 ),
-                JudgeModeConfig::Special { checker } => launch_special_case_judge(
+                JudgeModeConfig::Special { .. } => launch_special_case_judge(
+                    exec.clone(),
+                    self.find_relative_path(&case.inputfile_path).as_str(),
+                    case.answerfile_path
+                        .as_ref()
+                        .map(|f| self.find_relative_path(f))
+                        .as_deref(),
+                    jury_program.as_ref().unwrap(),
+                    self.limit_config.clone(),
+                    checker_limit.clone(),
+                ),
+                JudgeModeConfig::Script { .. } => launch_script_case_judge(
                     exec.clone(),
                     self.find_relative_path(&case.inputfile_path).as_str(),
-                    self.find_relative_path(&checker).as_str(),
+                    case.answerfile_path
+                        .as_ref()
+                        .map(|f| self.find_relative_path(f))
+                        .as_deref(),
+                    checker_script.as_ref().unwrap(),
                     self.limit_config.clone(),
                 ),
                 JudgeModeConfig::Interactive {
-                    interactor,
                     has_input,
+                    transcript_dir,
+                    max_runs,
+                    ..
                 } => {
                     let input = if has_input.clone() {
                         Some(self.find_relative_path(&case.inputfile_path))
                     } else {
                         None
                     };
+                    // transcripts are named after the index of cases
+                    let transcript_file = transcript_dir.as_ref().map(|f| {
+                        Path::new(&self.find_relative_path(f))
+                            .join(format!("{}.log", index + 1))
+                            .to_string_lossy()
+                            .to_string()
+                    });
                     launch_interactive_case_judge(
                         exec.clone(),
                         input,
-                        self.find_relative_path(&interactor).as_str(),
+                        jury_program.as_ref().unwrap(),
+                        self.limit_config.clone(),
+                        checker_limit.clone(),
+                        transcript_file.as_deref(),
+                        max_runs.clone(),
+                    )
+                }
+                // the submission has been built with grader, and is judged as a normal one.
+                JudgeModeConfig::Grader {
+                    comparision_mode, ..
+                } => launch_normal_case_judge(
+                    exec.clone(),
+                    self.find_relative_path(&case.inputfile_path).as_str(),
+                    self.find_relative_path(&case.answerfile_path.as_ref().unwrap())
+                        .as_str(),
+                    self.limit_config.clone(),
+                    comparision_mode,
+                ),
+                JudgeModeConfig::Communication {
+                    processes,
+                    wiring,
+                    has_input,
+                    ..
+                } => {
+                    let input = if has_input.clone() {
+                        Some(self.find_relative_path(&case.inputfile_path))
+                    } else {
+                        None
+                    };
+                    // the submission plays every process
+                    launch_communication_case_judge(
+                        vec![exec.clone(); *processes as usize],
+                        input,
+                        jury_program.as_ref().unwrap(),
+                        wiring.clone(),
                         self.limit_config.clone(),
+                        checker_limit.clone(),
                     )
                 }
             }?;
@@ -204,11 +416,61 @@ impl<'a> ProblemConfig<'a> {
 
         Ok(judge_results)
     }
+
+    /// Judge an output-only problem, whose outputs are submitted as a directory or a zip archive.
+    ///
+    /// The output of a case is the file named after its input file, with suffix `.out`,
+    /// like `1.out` for `data/1.in`.
+    pub fn judge_outputs(&self, outputs: &str) -> Result<Vec<JudgeResult>> {
+        let check = match &self.judge_mode {
+            JudgeModeConfig::Normal { comparision_mode }
+            | JudgeModeConfig::Grader {
+                comparision_mode, ..
+            } => OutputCheck::Comparision(comparision_mode.into()),
+            JudgeModeConfig::Special { checker } => OutputCheck::Checker(
+                prepare_jury_program(&self.find_relative_path(checker))?,
+                self.checker_limit.clone().unwrap_or(DEFAULT_CHECKER_LIMIT),
+            ),
+            JudgeModeConfig::Script { checker } => {
+                OutputCheck::Script(compile_checker_script(checker)?)
+            }
+            _ => {
+                return Err(Error::Argument(
+                    "outputs can only be judged by comparision or checker".into(),
+                ))
+            }
+        };
+        let mut outputs = collect_outputs(outputs)?;
+
+        let mut judge_results = Vec::new();
+        for case in self.cases.iter() {
+            let name = Path::new(&case.inputfile_path)
+                .with_extension("out")
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            let judge_result = launch_output_only_case_judge(
+                outputs.remove(&name),
+                self.find_relative_path(&case.inputfile_path).as_str(),
+                case.answerfile_path
+                    .as_ref()
+                    .map(|f| self.find_relative_path(f))
+                    .as_deref(),
+                &check,
+            )?;
+
+            judge_results.push(judge_result);
+        }
+
+        Ok(judge_results)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compile::{CompileLimit, DEFAULT_COMPILE_LIMIT};
     #[test]
     fn serialize() {
         let problem = ProblemConfig {
@@ -217,6 +479,7 @@ mod tests {
                 time_limit: 1,
                 memory_limit: 2,
             },
+            checker_limit: None,
             judge_mode: JudgeModeConfig::Normal {
                 comparision_mode: ComparisionModeConfig::Line,
             },
@@ -227,9 +490,30 @@ mod tests {
             path: "../test_dep/problem".into(),
             input_lint: None,
             answer_lint: None,
+            compilers: vec![(
+                "cpp".to_string(),
+                CompilerOptions {
+                    standard: Some("c++20".into()),
+                    backend: None,
+                    flags: vec!["-DONLINE_JUDGE".into()],
+                    limit: Some(CompileLimit {
+                        time_limit: 20000,
+                        ..DEFAULT_COMPILE_LIMIT
+                    }),
+                },
+            )]
+            .into_iter()
+            .collect(),
         };
         let s = serde_yaml::to_string(&problem).unwrap();
         println!("{}", s);
+
+        let problem = ProblemConfig::from_string(&s).unwrap();
+        assert_eq!(problem.compilers["cpp"].standard.as_deref(), Some("c++20"));
+        assert_eq!(
+            problem.compilers["cpp"].limit.as_ref().map(|f| f.time_limit),
+            Some(20000)
+        );
     }
 
     #[test]
@@ -0,0 +1,122 @@
+use std::{fmt, fs::File, io::Write};
+
+use crate::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    UserToInteractor,
+    InteractorToUser,
+}
+
+#[derive(Clone)]
+pub struct TranscriptRecord {
+    /// Time(ms) since the interaction started.
+    pub time: u64,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Debug for TranscriptRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // bytes are shown as text for being readable, the raw ones are kept in `data`.
+        f.debug_struct("TranscriptRecord")
+            .field("time", &self.time)
+            .field("direction", &self.direction)
+            .field("data", &String::from_utf8_lossy(&self.data))
+            .finish()
+    }
+}
+
+/// Bytes passed between user program and interactor, in the order they are passed.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub records: Vec<TranscriptRecord>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, time: u64, direction: Direction, data: Vec<u8>) {
+        self.records.push(TranscriptRecord {
+            time,
+            direction,
+            data,
+        });
+    }
+
+    /// Get all bytes passed in one direction as a whole.
+    pub fn collect(&self, direction: Direction) -> Vec<u8> {
+        self.records
+            .iter()
+            .filter(|f| f.direction == direction)
+            .flat_map(|f| f.data.iter().cloned())
+            .collect()
+    }
+
+    /// Write records one by one, each of which is a header line followed by its raw bytes and a LF.
+    ///
+    /// ```text
+    /// [12ms] interactor -> user, 2 bytes
+    /// 0
+    ///
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for record in self.records.iter() {
+            let direction = match record.direction {
+                Direction::UserToInteractor => "user -> interactor",
+                Direction::InteractorToUser => "interactor -> user",
+            };
+            writeln!(
+                writer,
+                "[{}ms] {}, {} bytes",
+                record.time,
+                direction,
+                record.data.len()
+            )?;
+            writer.write_all(&record.data)?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut file = File::create(path)?;
+        self.write_to(&mut file)?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_safe() -> Result<()> {
+        let mut transcript = Transcript::new();
+        transcript.push(0, Direction::InteractorToUser, vec![0u8, 255u8]);
+        transcript.push(1, Direction::UserToInteractor, "1\n".to_string().into_bytes());
+        transcript.push(2, Direction::InteractorToUser, vec![10u8]);
+
+        assert_eq!(
+            transcript.collect(Direction::InteractorToUser),
+            vec![0u8, 255u8, 10u8]
+        );
+
+        let mut buf = Vec::new();
+        transcript.write_to(&mut buf)?;
+        let mut expected = b"[0ms] interactor -> user, 2 bytes\n".to_vec();
+        expected.extend(vec![0u8, 255u8, 10u8]);
+        expected.extend(b"[1ms] user -> interactor, 2 bytes\n1\n\n".to_vec());
+        expected.extend(b"[2ms] interactor -> user, 1 bytes\n\n\n".to_vec());
+        assert_eq!(buf, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+
+use compile::Compiler;
+use tank_core::{
+    compile::{self, CompileResult},
+    problem::ProblemConfig,
+    error::{Error, Result},
+    JudgeStatus,
+};
+
+#[test]
+fn grader_accept() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/grader/problem.yaml")?;
+    let grader = judge.grader_of("cpp")?.unwrap();
+
+    let compiler = compile::gpp::CompilerGPP::new().unwrap();
+    let program = compiler.compile_with_grader(
+        fs::read_to_string("../test_dep/grader/solution.cpp").unwrap(),
+        &grader,
+    );
+    assert!(matches!(program, CompileResult::OK(_)));
+    let program = match program {
+        compile::CompileResult::OK(program) => program,
+        _ => unreachable!(),
+    };
+
+    let result = &judge.judge(program)?[0];
+
+    assert!(matches!(result.status, JudgeStatus::Accept));
+
+    Ok(())
+}
+
+#[test]
+fn grader_missing_function() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/grader/problem.yaml")?;
+    let grader = judge.grader_of("cpp")?.unwrap();
+
+    let compiler = compile::gpp::CompilerGPP::new().unwrap();
+    let program = compiler.compile_with_grader("int main(){}".into(), &grader);
+    assert!(matches!(program, CompileResult::CompileError(_)));
+
+    Ok(())
+}
+
+#[test]
+fn grader_missing_language() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/grader/problem.yaml")?;
+    assert!(matches!(judge.grader_of("python"), Err(Error::NotFound(_))));
+    // graders are found by language rather than suffix
+    assert!(matches!(judge.grader_of("cxx"), Err(Error::NotFound(_))));
+
+    let judge = ProblemConfig::from_file("../test_dep/normal/problem.yaml")?;
+    assert!(judge.grader_of("python")?.is_none());
+
+    Ok(())
+}
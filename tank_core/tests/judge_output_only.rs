@@ -0,0 +1,65 @@
+use std::{fs, io::Write};
+
+use tank_core::{
+    error::{Error, Result},
+    problem::ProblemConfig,
+    JudgeStatus,
+};
+
+#[test]
+fn output_only_directory() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/normal/problem.yaml")?;
+
+    let result = judge.judge_outputs("../test_dep/output_only/outputs")?;
+
+    assert!(matches!(result[0].status, JudgeStatus::Accept));
+    assert!(matches!(result[1].status, JudgeStatus::WrongAnswer));
+    // output of the third case is missing
+    assert!(matches!(result[2].status, JudgeStatus::WrongAnswer));
+
+    Ok(())
+}
+
+#[test]
+fn output_only_zip() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/normal/problem.yaml")?;
+
+    let result = judge.judge_outputs("../test_dep/output_only/outputs.zip")?;
+
+    assert!(matches!(result[0].status, JudgeStatus::Accept));
+    assert!(matches!(result[1].status, JudgeStatus::WrongAnswer));
+    assert!(matches!(result[2].status, JudgeStatus::WrongAnswer));
+
+    Ok(())
+}
+
+#[test]
+fn output_only_duplicate_name() -> Result<()> {
+    let judge = ProblemConfig::from_file("../test_dep/normal/problem.yaml")?;
+    let dir = tempfile::TempDir::new()?;
+
+    // outputs in different directories would overwrite each other
+    let outputs = dir.path().join("outputs");
+    for sub in &["a", "b"] {
+        fs::create_dir_all(outputs.join(sub))?;
+        fs::write(outputs.join(sub).join("1.out"), "1")?;
+    }
+    assert!(matches!(
+        judge.judge_outputs(&outputs.to_string_lossy()),
+        Err(Error::Data(_))
+    ));
+
+    let archive = dir.path().join("outputs.zip");
+    let mut writer = zip::ZipWriter::new(fs::File::create(&archive)?);
+    for name in &["a/1.out", "b/1.out"] {
+        writer.start_file(*name, zip::write::FileOptions::default())?;
+        writer.write_all(b"1")?;
+    }
+    writer.finish()?;
+    assert!(matches!(
+        judge.judge_outputs(&archive.to_string_lossy()),
+        Err(Error::Data(_))
+    ));
+
+    Ok(())
+}
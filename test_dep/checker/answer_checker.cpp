@@ -0,0 +1,27 @@
+#include <iostream>
+#include <fstream>
+using namespace std;
+
+// compare output with answer, rather than input
+int main(int argc,char* argv[]){
+    if(argc < 4) {
+        return 1;
+    }
+    ifstream output(argv[2]);
+    ifstream answer(argv[3]);
+
+    string s1, s2;
+    while(answer >> s2) {
+        if(!(output >> s1) || s1 != s2) {
+            cout << "different" << endl << "" << endl;
+            return 0;
+        }
+    }
+    if(output >> s1) {
+        cout << "different" << endl << "" << endl;
+    } else {
+        cout << "same" << endl << "" << endl;
+    }
+
+    return 0;
+}
@@ -0,0 +1,24 @@
+#include <iostream>
+#include <fstream>
+using namespace std;
+
+// usage: manager <from user 0> <to user 0> <from user 1> <to user 1>
+int main(int argc, char **argv){
+    int n = (argc - 1) / 2;
+    bool ok = true;
+    for(int i = 0; i < n; i++){
+        ifstream fin(argv[1 + i * 2]);
+        ofstream fout(argv[2 + i * 2]);
+        fout << 21 << endl;
+        int x; fin >> x;
+        if(x != 21 * (i + 1)) ok = false;
+    }
+
+    if(ok){
+        cerr<<"same"<<endl;
+    }else{
+        cerr<<"different"<<endl;
+    }
+
+    return 0;
+}
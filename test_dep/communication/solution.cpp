@@ -0,0 +1,13 @@
+#include <iostream>
+#include <cstdlib>
+using namespace std;
+
+// the index of process is given as the last argument
+int main(int argc, char **argv){
+    int index = atoi(argv[argc - 1]);
+    int x;
+    cin >> x;
+    cout << x * (index + 1) << endl;
+
+    return 0;
+}
@@ -0,0 +1,11 @@
+#include <iostream>
+#include "task.h"
+using namespace std;
+
+int main(){
+    long long a, b;
+    cin >> a >> b;
+    cout << add(a, b) << endl;
+
+    return 0;
+}